@@ -0,0 +1,805 @@
+//! use launchd agents to manage startup.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::warn;
+use plist::{Dictionary, Value};
+
+use super::{
+    ONESHOT_COMMENT, StartupBackend, file_id, home_dir, merged_env, parse_error, parse_header,
+};
+use crate::{
+    DEFAULT_RESTART_DELAY, Error, Marker, OutputMode, Restart, Result, StartupItem, State, Status,
+    runner::{CommandRunner, ProcessRunner},
+    utils::{join_command, split_command},
+};
+
+/// `$0` of the shell running the command of an item which is removed after its
+/// first successful run.
+const ONCE_ARG0: &str = "user-startup-once";
+/// The script of that shell. The path of the plist is its first argument, so
+/// it is never parsed as shell code.
+const ONCE_SCRIPT: &str = r#"plist="$1"; shift; "$@" && rm -f -- "$plist""#;
+
+/// Wrap a line in an XML comment. `--` must not appear in a comment, so a dash
+/// following a dash is escaped with a backslash, as are backslashes.
+fn comment(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || (c == '-' && escaped.ends_with('-')) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    format!("<!-- {escaped} -->")
+}
+
+/// The reverse of [`comment`].
+fn uncomment(line: &str) -> Option<String> {
+    let s = line.strip_prefix("<!-- ")?.strip_suffix(" -->")?;
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+/// Strip the XML declaration from the content of a plist.
+fn strip_declaration(content: &str) -> &str {
+    content
+        .trim_start()
+        .strip_prefix("<?xml")
+        .and_then(|rest| rest.split_once("?>"))
+        .map_or(content, |(_, rest)| rest)
+}
+
+/// Serialize a plist, with `comments` after the XML declaration.
+fn to_xml(dict: Dictionary, comments: &[String]) -> Result<String> {
+    let mut xml = vec![];
+    Value::Dictionary(dict)
+        .to_writer_xml(&mut xml)
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+    let xml = String::from_utf8(xml).expect("plist writes UTF-8");
+    // Comments must follow the XML declaration.
+    let (declaration, body) = xml.split_once('\n').unwrap_or_default();
+    Ok(format!("{declaration}\n{}\n{body}\n", comments.join("\n")))
+}
+
+/// Read the plist at `path`, returning its root dictionary and its leading
+/// comments.
+fn read_dict(path: &Path) -> Result<(Dictionary, Vec<String>)> {
+    let content = fs::read_to_string(path).map_err(Error::file(path))?;
+    let dict = Value::from_reader_xml(content.as_bytes())
+        .map_err(|e| parse_error(path, e.to_string()))?
+        .into_dictionary()
+        .ok_or_else(|| parse_error(path, "the root is not a dictionary"))?;
+    let comments = strip_declaration(&content)
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| line.starts_with("<!--"))
+        .map(str::to_string)
+        .collect();
+    Ok((dict, comments))
+}
+
+fn is_disabled(dict: &Dictionary) -> bool {
+    dict.get("Disabled").and_then(Value::as_boolean) == Some(true)
+}
+
+/// Set or remove the `Disabled` key of the plist at `path`, keeping its other
+/// keys and leading comments. Returns the label of the agent.
+fn set_disabled(runner: &dyn CommandRunner, path: &Path, disabled: bool) -> Result<String> {
+    let (mut dict, comments) = read_dict(path)?;
+    if disabled {
+        dict.insert("Disabled".into(), true.into());
+    } else {
+        dict.remove("Disabled");
+    }
+    let label = label(&dict, path);
+    runner.write_file(path, &to_xml(dict, &comments)?)?;
+    Ok(label)
+}
+
+/// The label of the agent, falling back to the id of its file.
+fn label(dict: &Dictionary, path: &Path) -> String {
+    dict.get("Label")
+        .and_then(Value::as_string)
+        .map_or_else(|| file_id(path), str::to_string)
+}
+
+/// Parse the output of `launchctl print` for a service. Only the top level
+/// properties are read, as nested blocks repeat keys like `state`.
+fn parse_print(output: &str) -> Status {
+    let value = |key: &str| {
+        output.lines().find_map(|line| {
+            let line = line.strip_prefix('\t')?;
+            let (k, v) = line.split_once(" = ")?;
+            (!k.starts_with(char::is_whitespace) && k == key).then_some(v.trim())
+        })
+    };
+    // e.g. `78: EX_CONFIG`, or `(never exited)`.
+    let last_exit_code =
+        value("last exit code").and_then(|code| code.split(':').next()?.parse().ok());
+    Status {
+        state: match value("state") {
+            Some("running") => State::Active,
+            _ if last_exit_code.is_some_and(|code| code != 0) => State::Failed,
+            _ => State::Inactive,
+        },
+        pid: value("pid").and_then(|pid| pid.parse().ok()),
+        started: None,
+        last_exit_code,
+        restarts: value("runs")
+            .and_then(|runs| runs.parse::<u32>().ok())
+            .map(|runs| runs.saturating_sub(1)),
+    }
+}
+
+/// Launchd agents in `~/Library/LaunchAgents`.
+pub struct Launchd {
+    config_dir: PathBuf,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Launchd {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_config_dir(
+            home_dir()?.join("Library").join("LaunchAgents"),
+        ))
+    }
+
+    /// Use `config_dir` instead of the default directory.
+    pub fn with_config_dir(config_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            config_dir: config_dir.into(),
+            runner: Arc::new(ProcessRunner),
+        }
+    }
+
+    /// Run `launchctl` with `runner` instead of spawning processes.
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// The launchd domain of the agents of the current user, `gui/<uid>`.
+    fn domain(&self) -> Result<String> {
+        let output = self.runner.query_checked("id", &["-u"])?;
+        Ok(format!("gui/{}", output.stdout.trim()))
+    }
+
+    /// The service target of the agent at `path`, `gui/<uid>/<label>`. An
+    /// agent not written yet, e.g. in a dry run, is labelled by its file name
+    /// like every agent user-startup writes.
+    fn service_target(&self, path: &Path) -> Result<String> {
+        let label = if path.exists() {
+            let (dict, _) = read_dict(path)?;
+            label(&dict, path)
+        } else {
+            file_id(path)
+        };
+        Ok(format!("{}/{label}", self.domain()?))
+    }
+
+    fn launchctl(&self, args: &[&str]) -> Result<()> {
+        self.runner.run_checked("launchctl", args)?;
+        Ok(())
+    }
+}
+
+impl StartupBackend for Launchd {
+    fn name(&self) -> &'static str {
+        "launchd"
+    }
+
+    fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    fn file_ext(&self) -> &'static str {
+        ".plist"
+    }
+
+    fn runner(&self) -> &dyn CommandRunner {
+        &*self.runner
+    }
+
+    /// The label of the agent is the id of the item, so that it is unique.
+    fn render(&self, item: &StartupItem) -> Result<String> {
+        let mut argv = split_command(&item.command)
+            .ok_or_else(|| Error::InvalidCommand(item.command.clone()))?;
+        if item.oneshot && item.once {
+            // launchd cannot run anything after the command, so a shell does.
+            argv.splice(
+                0..0,
+                [
+                    "/bin/sh",
+                    "-c",
+                    ONCE_SCRIPT,
+                    ONCE_ARG0,
+                    &item.path.to_string_lossy(),
+                ]
+                .map(str::to_string),
+            );
+        }
+        let label = if item.id.is_empty() {
+            &item.name
+        } else {
+            &item.id
+        };
+
+        let mut dict = Dictionary::new();
+        dict.insert("Label".into(), label.clone().into());
+        dict.insert(
+            "ProgramArguments".into(),
+            Value::Array(argv.into_iter().map(Value::String).collect()),
+        );
+        dict.insert("RunAtLoad".into(), true.into());
+        let keep_alive = match item.restart {
+            _ if item.oneshot => false.into(),
+            Restart::Never => false.into(),
+            Restart::OnFailure => {
+                let mut keep_alive = Dictionary::new();
+                keep_alive.insert("SuccessfulExit".into(), false.into());
+                Value::Dictionary(keep_alive)
+            }
+            Restart::Always => true.into(),
+        };
+        dict.insert("KeepAlive".into(), keep_alive);
+        // launchd waits at least this long between two starts.
+        dict.insert(
+            "ThrottleInterval".into(),
+            item.restart_delay.unwrap_or(DEFAULT_RESTART_DELAY).into(),
+        );
+        if item.max_restarts.is_some() {
+            warn!("launchd cannot limit the restarts, --max-restarts is ignored");
+        }
+        if item.output_mode != OutputMode::Append
+            && (item.stdout.is_some() || item.stderr.is_some())
+        {
+            warn!(
+                "launchd always appends to the output files, --output-mode {} is ignored",
+                item.output_mode
+            );
+        }
+        if let Some(stdout) = &item.stdout {
+            dict.insert("StandardOutPath".into(), stdout.clone().into());
+        }
+        if let Some(stderr) = &item.stderr {
+            dict.insert("StandardErrorPath".into(), stderr.clone().into());
+        }
+        // launchd runs agents in `/` by default.
+        let cwd = match &item.cwd {
+            Some(cwd) => cwd.clone(),
+            None => home_dir()?.to_string_lossy().into_owned(),
+        };
+        dict.insert("WorkingDirectory".into(), cwd.into());
+        let env = merged_env(item)?;
+        if !env.is_empty() {
+            dict.insert(
+                "EnvironmentVariables".into(),
+                Value::Dictionary(
+                    env.into_iter()
+                        .map(|(k, v)| (k, Value::String(v)))
+                        .collect(),
+                ),
+            );
+        }
+
+        if !item.enabled {
+            dict.insert("Disabled".into(), true.into());
+        }
+
+        let mut comments = vec![
+            comment(&item.marker.unwrap_or_else(Marker::now).to_string()),
+            comment(&item.command),
+        ];
+        if item.oneshot {
+            comments.push(comment(ONESHOT_COMMENT));
+        }
+        to_xml(dict, &comments)
+    }
+
+    fn parse(&self, path: &Path, content: &str) -> Result<StartupItem> {
+        let dict = Value::from_reader_xml(content.as_bytes())
+            .map_err(|e| parse_error(path, e.to_string()))?
+            .into_dictionary()
+            .ok_or_else(|| parse_error(path, "the root is not a dictionary"))?;
+        let string = |key| dict.get(key).and_then(Value::as_string);
+
+        let (marker, comment, flags) = parse_header(strip_declaration(content), uncomment);
+        let command = match marker {
+            Some(_) => comment,
+            None => dict
+                .get("ProgramArguments")
+                .and_then(Value::as_array)
+                .map(|argv| {
+                    let argv = argv.iter().filter_map(Value::as_string);
+                    join_command(&argv.map(str::to_string).collect::<Vec<_>>())
+                })
+                .or_else(|| string("Program").map(str::to_string)),
+        }
+        .filter(|command| !command.is_empty())
+        .ok_or_else(|| parse_error(path, "no command found"))?;
+
+        let mut item = StartupItem::new(command);
+        if let Some(label) = string("Label") {
+            item.name = label.to_string();
+        }
+        item.id = file_id(path);
+        item.path = path.to_path_buf();
+        item.stdout = string("StandardOutPath").map(str::to_string);
+        item.stderr = string("StandardErrorPath").map(str::to_string);
+        // The home directory is rendered for no working directory.
+        item.cwd = string("WorkingDirectory")
+            .filter(|cwd| home_dir().is_ok_and(|home| Path::new(cwd) != home))
+            .map(str::to_string);
+        if let Some(env) = dict
+            .get("EnvironmentVariables")
+            .and_then(Value::as_dictionary)
+        {
+            item.env = env
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_string()?.to_string())))
+                .collect();
+        }
+        item.restart = match dict.get("KeepAlive") {
+            Some(Value::Boolean(true)) => Restart::Always,
+            Some(Value::Dictionary(_)) => Restart::OnFailure,
+            _ => Restart::Never,
+        };
+        item.once = dict
+            .get("ProgramArguments")
+            .and_then(Value::as_array)
+            .and_then(|argv| argv.get(3))
+            .and_then(Value::as_string)
+            == Some(ONCE_ARG0);
+        // launchd has no notion of a one-shot job, so it is kept in a comment.
+        item.oneshot = item.once || flags.iter().any(|flag| flag == ONESHOT_COMMENT);
+        item.restart_delay = dict
+            .get("ThrottleInterval")
+            .and_then(Value::as_unsigned_integer);
+        item.enabled = !is_disabled(&dict);
+        item.marker = marker;
+        Ok(item)
+    }
+
+    fn is_enabled(&self, path: &Path) -> bool {
+        read_dict(path).is_ok_and(|(dict, _)| !is_disabled(&dict))
+    }
+
+    /// Remove the `Disabled` key and enable the label in launchd.
+    fn enable(&self, path: &Path) -> Result<()> {
+        let label = set_disabled(&*self.runner, path, false)?;
+        self.launchctl(&["enable", &format!("{}/{label}", self.domain()?)])
+    }
+
+    /// Set the `Disabled` key and disable the label in launchd, which is
+    /// remembered even if the key is ignored.
+    fn disable(&self, path: &Path) -> Result<()> {
+        let label = set_disabled(&*self.runner, path, true)?;
+        self.launchctl(&["disable", &format!("{}/{label}", self.domain()?)])
+    }
+
+    fn start(&self, path: &Path) -> Result<()> {
+        self.launchctl(&["kickstart", &self.service_target(path)?])
+    }
+
+    fn stop(&self, path: &Path) -> Result<()> {
+        self.launchctl(&["kill", "SIGTERM", &self.service_target(path)?])
+    }
+
+    /// `kickstart -k` kills the running instance before starting it again.
+    fn restart(&self, path: &Path) -> Result<()> {
+        self.launchctl(&["kickstart", "-k", &self.service_target(path)?])
+    }
+
+    /// An agent which is not loaded cannot be printed, and is inactive.
+    fn status(&self, path: &Path) -> Result<Status> {
+        let output = self
+            .runner
+            .query("launchctl", &["print", &self.service_target(path)?])?;
+        if !output.success() {
+            return Ok(Status::default());
+        }
+        Ok(parse_print(&output.stdout))
+    }
+
+    /// Write the agent and load it into the domain of the user, which also
+    /// starts it as `RunAtLoad` is set.
+    ///
+    /// launchd remembers a disabled label after its agent is removed, so the
+    /// label is enabled first, as it may be the id of a removed item.
+    fn install(&self, path: &Path, content: &str) -> Result<()> {
+        self.runner.write_file(path, content)?;
+        let label = Value::from_reader_xml(content.as_bytes())
+            .ok()
+            .and_then(Value::into_dictionary)
+            .map_or_else(|| file_id(path), |dict| label(&dict, path));
+        let domain = self.domain()?;
+        self.launchctl(&["enable", &format!("{domain}/{label}")])?;
+        self.launchctl(&["bootstrap", &domain, &path.to_string_lossy()])
+    }
+
+    /// Write the agent, and reload it if it is loaded, as launchd keeps
+    /// running the old job until then. Reloading starts it again.
+    fn update(&self, path: &Path, content: &str) -> Result<()> {
+        let domain = self.domain()?;
+        let (dict, _) = read_dict(path)?;
+        let target = format!("{domain}/{}", label(&dict, path));
+        let loaded = self
+            .runner
+            .query("launchctl", &["print", &target])?
+            .success();
+        let path_str = path.to_string_lossy();
+        if loaded {
+            self.launchctl(&["bootout", &domain, &path_str])?;
+        }
+        self.runner.write_file(path, content)?;
+        if loaded {
+            self.launchctl(&["bootstrap", &domain, &path_str])?;
+        }
+        Ok(())
+    }
+
+    /// Unload the agent and remove it. An agent which is not loaded can still
+    /// be removed, so a failed `bootout` is only a warning.
+    fn uninstall(&self, path: &Path) -> Result<()> {
+        let path_str = path.to_string_lossy();
+        if let Err(e) = self.launchctl(&["bootout", &self.domain()?, &path_str]) {
+            warn!("Failed to unload `{}`: {e}", path.display());
+        }
+        self.runner.remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{CommandOutput, RecordingRunner};
+
+    fn runner() -> RecordingRunner {
+        RecordingRunner::new().respond("id -u", CommandOutput::ok("501\n"))
+    }
+
+    #[test]
+    fn test_comment_roundtrip() {
+        for s in ["foo --bar", "a---b-", r"C:\dir\--x", "<!-- x -->"] {
+            let comment = comment(s);
+            assert!(!comment[4..comment.len() - 3].contains("--"), "{comment}");
+            assert_eq!(uncomment(&comment).unwrap(), s);
+        }
+    }
+
+    #[test]
+    fn test_render_once() {
+        let backend = Launchd::with_config_dir("/Users/user/Library/LaunchAgents");
+        let path = backend.config_dir().join("mount;$(id)&.plist");
+        let mut item = StartupItem::new("mount-share.sh --all").once();
+        item.path = path.clone();
+        let content = backend.render(&item).unwrap();
+        let dict = Value::from_reader_xml(content.as_bytes())
+            .unwrap()
+            .into_dictionary()
+            .unwrap();
+        assert_eq!(dict["KeepAlive"], Value::Boolean(false));
+        let argv = dict["ProgramArguments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            argv,
+            [
+                "/bin/sh",
+                "-c",
+                ONCE_SCRIPT,
+                ONCE_ARG0,
+                "/Users/user/Library/LaunchAgents/mount;$(id)&.plist",
+                "mount-share.sh",
+                "--all"
+            ]
+        );
+        let parsed = backend.parse(&path, &content).unwrap();
+        assert!(parsed.oneshot && parsed.once);
+        assert_eq!(parsed.command, item.command);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_once_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a;b $(touch x)&`touch y`.plist");
+        fs::write(&path, "").unwrap();
+        let run = |command: &str| {
+            std::process::Command::new("/bin/sh")
+                .current_dir(dir.path())
+                .args([
+                    "-c",
+                    ONCE_SCRIPT,
+                    ONCE_ARG0,
+                    &path.to_string_lossy(),
+                    command,
+                ])
+                .status()
+                .unwrap()
+        };
+        assert!(!run("false").success());
+        assert!(path.exists());
+        assert!(run("true").success());
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_render_valid_plist() {
+        let backend = Launchd::with_config_dir("/Users/user/Library/LaunchAgents");
+        let mut item =
+            StartupItem::new(r#"rclone mount "my drive:" ~/a&b --vfs-cache-mode <full>"#)
+                .stdout("/tmp/rclone.log")
+                .restart(Restart::Always);
+        item.id = "rclone1".to_string();
+        let content = backend.render(&item).unwrap();
+        assert!(content.starts_with("<?xml"));
+
+        let dict = Value::from_reader_xml(content.as_bytes())
+            .unwrap()
+            .into_dictionary()
+            .unwrap();
+        assert_eq!(dict["Label"].as_string(), Some("rclone1"));
+        let argv = dict["ProgramArguments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|arg| arg.as_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            argv,
+            [
+                "rclone",
+                "mount",
+                "my drive:",
+                "~/a&b",
+                "--vfs-cache-mode",
+                "<full>"
+            ]
+        );
+        assert_eq!(dict["RunAtLoad"].as_boolean(), Some(true));
+        assert_eq!(dict["KeepAlive"].as_boolean(), Some(true));
+        assert_eq!(dict["StandardOutPath"].as_string(), Some("/tmp/rclone.log"));
+        assert!(!dict.contains_key("StandardErrorPath"));
+        assert_eq!(
+            dict["WorkingDirectory"].as_string(),
+            Some(&*home_dir().unwrap().to_string_lossy())
+        );
+
+        let path = backend.config_dir().join("rclone1.plist");
+        let parsed = backend.parse(&path, &content).unwrap();
+        assert_eq!(parsed.command, item.command);
+        assert!(parsed.is_managed());
+        // The default working directory is not read back as one.
+        assert_eq!(parsed.cwd, None);
+    }
+
+    #[test]
+    fn test_parse_foreign_plist() {
+        let backend = Launchd::with_config_dir("/Users/user/Library/LaunchAgents");
+        let path = backend.config_dir().join("com.example.agent.plist");
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>com.example.agent</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>/usr/local/bin/agent</string>
+        <string>--config</string>
+        <string>/Users/user/my config.toml</string>
+    </array>
+</dict>
+</plist>
+"#;
+        let item = backend.parse(&path, content).unwrap();
+        assert!(!item.is_managed());
+        assert_eq!(item.name, "com.example.agent");
+        assert_eq!(
+            item.command,
+            "/usr/local/bin/agent --config '/Users/user/my config.toml'"
+        );
+    }
+
+    #[test]
+    fn test_install_uninstall_launchctl() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = Arc::new(runner());
+        let backend = Launchd::with_config_dir(dir.path()).with_runner(runner.clone());
+        let path = dir.path().join("syncthing.plist");
+        let path_str = path.to_string_lossy();
+
+        backend.install(&path, "<plist/>").unwrap();
+        assert!(path.exists());
+        backend.uninstall(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            runner.calls(),
+            [
+                "id -u".to_string(),
+                "launchctl enable gui/501/syncthing".to_string(),
+                format!("launchctl bootstrap gui/501 {path_str}"),
+                "id -u".to_string(),
+                format!("launchctl bootout gui/501 {path_str}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("syncthing.plist");
+        let path_str = path.to_string_lossy();
+        let mut item = StartupItem::new("syncthing");
+        item.id = "syncthing".to_string();
+        let loaded = Arc::new(runner());
+        let backend = Launchd::with_config_dir(dir.path()).with_runner(loaded.clone());
+        fs::write(&path, backend.render(&item).unwrap()).unwrap();
+
+        let content = backend
+            .render(&StartupItem::new("syncthing --no-browser"))
+            .unwrap();
+        backend.update(&path, &content).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        assert_eq!(
+            loaded.calls(),
+            [
+                "id -u".to_string(),
+                "launchctl print gui/501/syncthing".to_string(),
+                format!("launchctl bootout gui/501 {path_str}"),
+                format!("launchctl bootstrap gui/501 {path_str}"),
+            ]
+        );
+
+        // An agent which is not loaded is only written.
+        let not_loaded = Arc::new(runner().respond(
+            "launchctl print",
+            CommandOutput::failed(113, "Could not find service"),
+        ));
+        let backend = Launchd::with_config_dir(dir.path()).with_runner(not_loaded.clone());
+        backend.update(&path, &content).unwrap();
+        assert_eq!(
+            not_loaded.calls(),
+            ["id -u", "launchctl print gui/501/syncthing"]
+        );
+    }
+
+    #[test]
+    fn test_launchctl_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner =
+            Arc::new(runner().respond("launchctl", CommandOutput::failed(5, "Input/output error")));
+        let backend = Launchd::with_config_dir(dir.path()).with_runner(runner);
+        let path = dir.path().join("syncthing.plist");
+
+        let err = backend.install(&path, "<plist/>").unwrap_err();
+        assert!(matches!(err, Error::CommandFailed { code: Some(5), .. }));
+        // A failed bootout does not keep the agent from being removed.
+        backend.uninstall(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_enable_disable_start_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = Arc::new(runner());
+        let backend = Launchd::with_config_dir(dir.path()).with_runner(runner.clone());
+        let path = dir.path().join("syncthing.plist");
+        let mut item = StartupItem::new("syncthing --no-browser");
+        item.id = "syncthing".to_string();
+        item.marker = Some(Marker::now());
+        fs::write(&path, backend.render(&item).unwrap()).unwrap();
+
+        backend.disable(&path).unwrap();
+        let disabled = backend.read(&path).unwrap();
+        assert!(!disabled.enabled);
+        assert_eq!(disabled.command, item.command);
+        assert_eq!(disabled.marker, item.marker);
+
+        backend.enable(&path).unwrap();
+        assert!(backend.read(&path).unwrap().enabled);
+        assert!(!fs::read_to_string(&path).unwrap().contains("Disabled"));
+
+        backend.start(&path).unwrap();
+        backend.stop(&path).unwrap();
+        backend.restart(&path).unwrap();
+        assert_eq!(
+            runner.calls(),
+            [
+                "id -u",
+                "launchctl disable gui/501/syncthing",
+                "id -u",
+                "launchctl enable gui/501/syncthing",
+                "id -u",
+                "launchctl kickstart gui/501/syncthing",
+                "id -u",
+                "launchctl kill SIGTERM gui/501/syncthing",
+                "id -u",
+                "launchctl kickstart -k gui/501/syncthing",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_print() {
+        let output = "gui/501/syncthing = {
+\tactive count = 1
+\tpath = /Users/user/Library/LaunchAgents/syncthing.plist
+\tstate = running
+
+\tprogram = syncthing
+\targuments = {
+\t\tsyncthing
+\t\t--no-browser
+\t}
+
+\truns = 3
+\tpid = 1234
+\tlast exit code = 1
+
+\tevent triggers = {
+\t\tstate = inactive
+\t}
+}
+";
+        assert_eq!(
+            parse_print(output),
+            Status {
+                state: State::Active,
+                pid: Some(1234),
+                started: None,
+                last_exit_code: Some(1),
+                restarts: Some(2),
+            }
+        );
+
+        let output = "gui/501/syncthing = {
+\tstate = not running
+\truns = 1
+\tlast exit code = 78: EX_CONFIG
+}
+";
+        let status = parse_print(output);
+        assert_eq!(status.state, State::Failed);
+        assert_eq!(status.last_exit_code, Some(78));
+        assert_eq!(status.restarts, Some(0));
+        let status = parse_print("\tstate = not running\n\tlast exit code = (never exited)\n");
+        assert_eq!(status.state, State::Inactive);
+        assert_eq!(status.last_exit_code, None);
+    }
+
+    #[test]
+    fn test_status_not_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = Arc::new(runner().respond(
+            "launchctl print",
+            CommandOutput::failed(113, "Could not find service"),
+        ));
+        let backend = Launchd::with_config_dir(dir.path()).with_runner(runner);
+        let path = dir.path().join("syncthing.plist");
+        fs::write(
+            &path,
+            backend.render(&StartupItem::new("syncthing")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(backend.status(&path).unwrap(), Status::default());
+    }
+}
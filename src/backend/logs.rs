@@ -0,0 +1,155 @@
+//! Show the output of startup items.
+
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+use log::warn;
+
+use crate::Result;
+
+/// How often followed log files are checked for new output.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Which logs to show.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogOptions {
+    /// Keep showing new output until interrupted.
+    pub follow: bool,
+    /// Only show the last lines. All lines if `None`.
+    pub lines: Option<usize>,
+    /// Only show output since this time, e.g. `2026-10-18 10:00` or `1h ago`.
+    /// Only supported by the journal.
+    pub since: Option<String>,
+}
+
+/// The byte offset where the last `lines` lines of `content` start. A trailing
+/// newline does not start another line.
+fn last_lines_start(content: &[u8], lines: usize) -> usize {
+    let body = content.strip_suffix(b"\n").unwrap_or(content);
+    if lines == 0 {
+        return content.len();
+    }
+    body.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, byte)| **byte == b'\n')
+        .nth(lines - 1)
+        .map_or(0, |(i, _)| i + 1)
+}
+
+/// Print the log files to `out` like `tail` does, with a header for each file
+/// if there are several. Files which do not exist yet are shown as empty, so
+/// they can be followed until the command writes to them.
+pub(crate) fn tail_files(
+    paths: &[PathBuf],
+    options: &LogOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if options.since.is_some() {
+        warn!("--since is ignored for log files");
+    }
+    let mut paths = paths.to_vec();
+    paths.dedup();
+    // The header is only repeated when the output switches to another file.
+    let mut last = None;
+    let mut header = |out: &mut dyn Write, i: usize| -> io::Result<()> {
+        if paths.len() > 1 && last != Some(i) {
+            writeln!(out, "==> {} <==", paths[i].display())?;
+        }
+        last = Some(i);
+        Ok(())
+    };
+
+    let mut offsets = vec![];
+    for (i, path) in paths.iter().enumerate() {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                warn!("`{}` does not exist yet", path.display());
+                vec![]
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let start = options
+            .lines
+            .map_or(0, |lines| last_lines_start(&content, lines));
+        header(out, i)?;
+        out.write_all(&content[start..])?;
+        offsets.push(content.len() as u64);
+    }
+    out.flush()?;
+
+    if !options.follow {
+        return Ok(());
+    }
+    loop {
+        thread::sleep(FOLLOW_INTERVAL);
+        for (i, (path, offset)) in paths.iter().zip(&mut offsets).enumerate() {
+            let Ok(mut file) = fs::File::open(path) else {
+                continue;
+            };
+            let len = file.metadata()?.len();
+            if len < *offset {
+                // The file was truncated, e.g. rotated.
+                *offset = 0;
+            }
+            if len == *offset {
+                continue;
+            }
+            file.seek(SeekFrom::Start(*offset))?;
+            let mut new = vec![];
+            file.read_to_end(&mut new)?;
+            header(out, i)?;
+            out.write_all(&new)?;
+            out.flush()?;
+            *offset += new.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_lines_start() {
+        let content = b"a\nbb\nccc\n";
+        assert_eq!(&content[last_lines_start(content, 1)..], b"ccc\n");
+        assert_eq!(&content[last_lines_start(content, 2)..], b"bb\nccc\n");
+        assert_eq!(&content[last_lines_start(content, 5)..], content);
+        assert_eq!(&content[last_lines_start(content, 0)..], b"");
+        assert_eq!(&b"a\nb"[last_lines_start(b"a\nb", 1)..], b"b");
+    }
+
+    #[test]
+    fn test_tail_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdout = dir.path().join("out.log");
+        let stderr = dir.path().join("err.log");
+        fs::write(&stdout, "1\n2\n3\n").unwrap();
+        let options = LogOptions {
+            lines: Some(2),
+            ..Default::default()
+        };
+
+        let mut out = vec![];
+        tail_files(&[stdout.clone(), stdout.clone()], &options, &mut out).unwrap();
+        assert_eq!(out, b"2\n3\n");
+
+        let mut out = vec![];
+        tail_files(&[stdout.clone(), stderr.clone()], &options, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "==> {} <==\n2\n3\n==> {} <==\n",
+                stdout.display(),
+                stderr.display()
+            )
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! use the Windows Startup folder to manage startup.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::{
+    ONESHOT_COMMENT, StartupBackend, file_id, home_dir, merged_env, parse_error, parse_header,
+};
+use crate::{
+    Error, Marker, OutputMode, Restart, Result, StartupItem,
+    runner::{CommandRunner, ProcessRunner},
+};
+
+const COMMENT_PREFIX: &str = ":: ";
+/// Disabled scripts are moved into this subdirectory of the Startup folder,
+/// whose content is not run on startup.
+const DISABLED_DIR: &str = "disabled";
+
+fn comment(s: &str) -> String {
+    format!("{COMMENT_PREFIX}{s}")
+}
+
+fn escape_quotes(s: impl AsRef<str>) -> String {
+    s.as_ref().replace("\"", "^\"")
+}
+
+/// Render a variable as a `set` line. Quoting the whole assignment keeps
+/// special characters like `&` literal, and `%` is escaped by doubling it. A
+/// `"` would end the quotes and cannot be escaped, so it is rejected.
+fn set_line(key: &str, value: &str) -> Result<String> {
+    if key.contains('"') || value.contains('"') {
+        return Err(Error::InvalidEnv(key.to_string()));
+    }
+    Ok(format!(
+        "set \"{}={}\"\n",
+        key.replace('%', "%%"),
+        value.replace('%', "%%")
+    ))
+}
+
+/// Parse a line rendered by [`set_line`].
+fn parse_set_line(line: &str) -> Option<(String, String)> {
+    let assignment = line.strip_prefix("set \"")?.strip_suffix('"')?;
+    let (key, value) = assignment.split_once('=')?;
+    Some((key.replace("%%", "%"), value.replace("%%", "%")))
+}
+
+/// The prefix of the line changing the working directory, also across drives.
+const CD_PREFIX: &str = "cd /d ";
+/// The flag of `user-startup run` which removes the script after the first
+/// successful run.
+const REMOVE_FLAG: &str = "--remove-on-success";
+/// The working directory when the item has none.
+const HOME_DIR: &str = "%USERPROFILE%";
+
+/// The flags of `user-startup run` which restart or remove the command.
+/// Scripts without them run it once.
+fn restart_flags(item: &StartupItem) -> String {
+    if item.oneshot || item.restart == Restart::Never {
+        // `%~f0` is the path of the script.
+        return if item.oneshot && item.once {
+            format!(" {REMOVE_FLAG} \"%~f0\"")
+        } else {
+            String::new()
+        };
+    }
+    let mut flags = format!(" --restart {}", item.restart);
+    if let Some(delay) = item.restart_delay {
+        flags += &format!(" --restart-delay {delay}");
+    }
+    if let Some(max_restarts) = item.max_restarts {
+        flags += &format!(" --max-restarts {max_restarts}");
+    }
+    flags
+}
+
+/// Find the value of `flag` in a line of the script, which may be quoted.
+fn flag_value(line: &str, flag: &str) -> Option<String> {
+    let rest = line.split_once(&format!(" {flag} "))?.1.trim_start();
+    let value = match rest.strip_prefix('"') {
+        Some(rest) => rest.split_once('"')?.0,
+        None => rest.split_whitespace().next()?,
+    };
+    Some(value.to_string())
+}
+
+/// `.cmd` scripts in the Startup folder of the start menu. Every script there
+/// calls `user-startup run` to start its command without a console window.
+pub struct StartupFolder {
+    config_dir: PathBuf,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl StartupFolder {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_config_dir(
+            home_dir()?
+                .join("AppData")
+                .join("Roaming")
+                .join("Microsoft")
+                .join("Windows")
+                .join("Start Menu")
+                .join("Programs")
+                .join("Startup"),
+        ))
+    }
+
+    /// Use `config_dir` instead of the default directory.
+    pub fn with_config_dir(config_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            config_dir: config_dir.into(),
+            runner: Arc::new(ProcessRunner),
+        }
+    }
+
+    /// Change the scripts with `runner` instead of the file system.
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    fn disabled_dir(&self) -> PathBuf {
+        self.config_dir.join(DISABLED_DIR)
+    }
+
+    /// Move the script at `path` into `dir`, unless it is already there.
+    fn move_to(&self, path: &Path, dir: &Path) -> Result<()> {
+        if path.parent() == Some(dir) {
+            return Ok(());
+        }
+        self.runner
+            .rename(path, &dir.join(path.file_name().unwrap_or_default()))
+    }
+}
+
+impl StartupBackend for StartupFolder {
+    fn name(&self) -> &'static str {
+        "startup-folder"
+    }
+
+    fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    fn file_ext(&self) -> &'static str {
+        ".cmd"
+    }
+
+    fn runner(&self) -> &dyn CommandRunner {
+        &*self.runner
+    }
+
+    fn search_dirs(&self) -> Vec<PathBuf> {
+        vec![self.config_dir.clone(), self.disabled_dir()]
+    }
+
+    /// The Startup folder has no notion of a name, so it is ignored. The
+    /// command is restarted by `user-startup run`.
+    fn render(&self, item: &StartupItem) -> Result<String> {
+        let env = merged_env(item)?
+            .iter()
+            .map(|(key, value)| set_line(key, value))
+            .collect::<Result<String>>()?;
+        // `%` would expand variables in the paths.
+        let output = |flag, file: &Option<String>| {
+            file.as_ref().map_or(String::new(), |file| {
+                format!("{flag} \"{}\"", file.replace('%', "%%"))
+            })
+        };
+        let cwd = item
+            .cwd
+            .as_deref()
+            .map_or(HOME_DIR.to_string(), |cwd| cwd.replace('%', "%%"));
+        Ok(format!(
+            r#"{marker}
+{prefixed_cmd}
+{oneshot}{CD_PREFIX}"{cwd}"
+{env}"{self_bin}" run "{cmd}" {stdout} {stderr}{output_mode}{restart}
+"#,
+            self_bin = std::env::current_exe()?.display(),
+            marker = comment(&item.marker.unwrap_or_else(Marker::now).to_string()),
+            prefixed_cmd = comment(&item.command),
+            oneshot = if item.oneshot {
+                format!("{}\n", comment(ONESHOT_COMMENT))
+            } else {
+                String::new()
+            },
+            cmd = escape_quotes(&item.command),
+            stdout = output("--stdout", &item.stdout),
+            stderr = output("--stderr", &item.stderr),
+            output_mode = match item.output_mode {
+                OutputMode::Append => String::new(),
+                mode => format!(" --output-mode {mode}"),
+            },
+            restart = restart_flags(item),
+        ))
+    }
+
+    fn parse(&self, path: &Path, content: &str) -> Result<StartupItem> {
+        let (marker, command, flags) =
+            parse_header(content, |line| line.strip_prefix(COMMENT_PREFIX));
+        let command = command.ok_or_else(|| parse_error(path, "no command comment found"))?;
+        let run_line = content
+            .lines()
+            .find(|line| line.starts_with('"'))
+            .unwrap_or_default();
+        let mut item = StartupItem::new(command);
+        item.id = file_id(path);
+        item.name = item.id.clone();
+        item.path = path.to_path_buf();
+        item.stdout = flag_value(run_line, "--stdout").map(|file| file.replace("%%", "%"));
+        item.stderr = flag_value(run_line, "--stderr").map(|file| file.replace("%%", "%"));
+        item.output_mode = flag_value(run_line, "--output-mode")
+            .and_then(|mode| mode.parse().ok())
+            .unwrap_or_default();
+        item.cwd = content
+            .lines()
+            .find_map(|line| line.strip_prefix(CD_PREFIX))
+            .map(|cwd| cwd.trim().trim_matches('"'))
+            .filter(|cwd| *cwd != HOME_DIR)
+            .map(|cwd| cwd.replace("%%", "%"));
+        item.env = content
+            .lines()
+            .filter_map(|line| parse_set_line(line.trim_end()))
+            .collect();
+        item.restart = flag_value(run_line, "--restart")
+            .and_then(|restart| restart.parse().ok())
+            .unwrap_or(Restart::Never);
+        item.once = run_line.contains(REMOVE_FLAG);
+        // A script runs its command once unless restarted, so whether it is a
+        // one-shot item is kept in a comment.
+        item.oneshot = item.once || flags.iter().any(|flag| flag == ONESHOT_COMMENT);
+        item.restart_delay = flag_value(run_line, "--restart-delay").and_then(|s| s.parse().ok());
+        item.max_restarts = flag_value(run_line, "--max-restarts").and_then(|s| s.parse().ok());
+        item.marker = marker;
+        Ok(item)
+    }
+
+    fn is_enabled(&self, path: &Path) -> bool {
+        path.parent() == Some(self.config_dir.as_path())
+    }
+
+    fn uninstall(&self, path: &Path) -> Result<()> {
+        self.runner.remove_file(path)
+    }
+
+    /// Move the script back into the Startup folder.
+    fn enable(&self, path: &Path) -> Result<()> {
+        self.move_to(path, &self.config_dir)
+    }
+
+    /// Move the script out of the Startup folder.
+    fn disable(&self, path: &Path) -> Result<()> {
+        self.move_to(path, &self.disabled_dir())
+    }
+
+    /// Run the script like on startup, which spawns the command without a
+    /// window and returns. The process is not tracked, so it cannot be
+    /// stopped or restarted.
+    fn start(&self, path: &Path) -> Result<()> {
+        if !cfg!(target_os = "windows") {
+            return Err(Error::Unsupported {
+                backend: self.name(),
+                operation: "start",
+            });
+        }
+        self.runner.run_checked(&path.to_string_lossy(), &[])?;
+        Ok(())
+    }
+}
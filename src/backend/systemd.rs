@@ -0,0 +1,864 @@
+//! use systemd to manage startup.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::warn;
+
+use super::{
+    LogOptions, StartupBackend, file_id, home_dir, parse_error, parse_header, tail_log_files,
+};
+use crate::{
+    DEFAULT_RESTART_DELAY, Error, Marker, OutputMode, Restart, Result, StartupItem, State, Status,
+    runner::{CommandRunner, ProcessRunner},
+    utils::split_command,
+};
+
+const COMMENT_PREFIX: &str = "# ";
+
+fn comment(s: &str) -> String {
+    format!("{COMMENT_PREFIX}{s}")
+}
+
+/// Find the values of the `key=value` lines in a unit file.
+fn unit_values<'a>(content: &'a str, key: &str) -> impl Iterator<Item = &'a str> {
+    content.lines().filter_map(move |line| {
+        line.trim()
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix('='))
+            .map(str::trim)
+    })
+}
+
+/// Find the value of the first `key=value` line in a unit file.
+fn unit_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    unit_values(content, key).next()
+}
+
+/// Quote a variable of `Environment=`, as described in `systemd.exec(5)`.
+/// Variables are not expanded there, so only specifiers (`%`) are escaped.
+fn quote_env(key: &str, value: &str) -> String {
+    let assignment = format!("{key}={value}")
+        .replace('%', "%%")
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#);
+    format!("\"{assignment}\"")
+}
+
+/// Split the value of an `Environment=` line into variables, the reverse of
+/// [`quote_env`]. Assignments are separated by whitespace unless quoted.
+fn parse_env(value: &str) -> Vec<(String, String)> {
+    let mut assignments = vec![];
+    // `None` between assignments, `Some` while inside one.
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.get_or_insert_default().extend(chars.next()),
+            '"' | '\'' if quote.is_none() => {
+                quote = Some(c);
+                current.get_or_insert_default();
+            }
+            c if quote == Some(c) => quote = None,
+            c if c.is_whitespace() && quote.is_none() => assignments.extend(current.take()),
+            c => current.get_or_insert_default().push(c),
+        }
+    }
+    assignments.extend(current);
+    assignments
+        .into_iter()
+        .filter_map(|assignment| {
+            let assignment = assignment.replace("%%", "%");
+            let (key, value) = assignment.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The prefixes of the executable in `ExecStart=` which change how systemd
+/// runs it. They are read after unquoting, so they cannot be escaped.
+const EXEC_PREFIXES: [char; 5] = ['@', '-', ':', '+', '!'];
+
+/// Serialize an argv into a command line of `ExecStart=`, as described in
+/// `systemd.service(5)`. Specifiers (`%`) and variables (`$`) are escaped, so
+/// systemd passes every argument literally.
+///
+/// The executable must not start with one of [`EXEC_PREFIXES`].
+fn quote_exec(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            let arg = arg.replace('%', "%%").replace('$', "$$");
+            let needs_quotes = arg.is_empty()
+                || arg.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\'));
+            if arg == ";" {
+                // A lone semicolon separates commands in older systemd.
+                r"\;".to_string()
+            } else if needs_quotes {
+                let escaped = arg
+                    .replace('\\', r"\\")
+                    .replace('"', r#"\""#)
+                    .replace('\n', r"\n")
+                    .replace('\t', r"\t");
+                format!("\"{escaped}\"")
+            } else {
+                arg
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The directories systemd searches for user units, in order of priority, as
+/// described in `systemd.unit(5)`. Control, transient and generator
+/// directories are left out since they are managed by systemd itself.
+///
+/// The first one is `$XDG_CONFIG_HOME/systemd/user`, where units are written.
+fn unit_search_dirs(home: &Path, env: impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    // Relative paths in XDG variables are invalid and should be ignored.
+    let var = |key: &str| env(key).filter(|value| Path::new(value).is_absolute());
+    let dir = |key: &str, default: PathBuf| var(key).map_or(default, PathBuf::from);
+    let dirs = |key: &str, default: &str| {
+        var(key)
+            .unwrap_or_else(|| default.to_string())
+            .split(':')
+            .filter(|dir| Path::new(dir).is_absolute())
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+    };
+
+    let mut res = vec![dir("XDG_CONFIG_HOME", home.join(".config"))];
+    res.extend(dirs("XDG_CONFIG_DIRS", "/etc/xdg"));
+    res.push("/etc/systemd".into());
+    res.extend(var("XDG_RUNTIME_DIR").map(PathBuf::from));
+    res.push("/run/systemd".into());
+    res.push(dir("XDG_DATA_HOME", home.join(".local").join("share")));
+    res.extend(dirs("XDG_DATA_DIRS", "/usr/local/share:/usr/share"));
+    res.push("/usr/local/lib/systemd".into());
+    res.push("/usr/lib/systemd".into());
+
+    res.into_iter()
+        .map(|dir| {
+            if dir.ends_with("systemd") {
+                dir.join("user")
+            } else {
+                dir.join("systemd").join("user")
+            }
+        })
+        .collect()
+}
+
+/// The unit properties queried by [`Systemd::status`].
+const STATUS_PROPERTIES: &str =
+    "ActiveState,MainPID,ExecMainStartTimestamp,ExecMainExitTimestamp,ExecMainStatus,NRestarts";
+
+/// Parse the output of `systemctl show` with [`STATUS_PROPERTIES`].
+fn parse_show(output: &str) -> Status {
+    let value = |key| unit_value(output, key).filter(|value| !value.is_empty());
+    Status {
+        state: match value("ActiveState") {
+            Some("active" | "activating" | "reloading" | "refreshing") => State::Active,
+            Some("failed") => State::Failed,
+            _ => State::Inactive,
+        },
+        pid: value("MainPID")
+            .and_then(|pid| pid.parse().ok())
+            .filter(|pid| *pid != 0),
+        started: value("ExecMainStartTimestamp").map(str::to_string),
+        // The status is only meaningful once the command exited.
+        last_exit_code: value("ExecMainExitTimestamp")
+            .and(value("ExecMainStatus"))
+            .and_then(|code| code.parse().ok()),
+        restarts: value("NRestarts").and_then(|n| n.parse().ok()),
+    }
+}
+
+/// Whether systemd searches `dir` for the units of the current user.
+fn is_searched(dir: &Path) -> bool {
+    let (Ok(home), Ok(dir)) = (home_dir(), std::path::absolute(dir)) else {
+        return false;
+    };
+    unit_search_dirs(&home, |key| std::env::var(key).ok()).contains(&dir)
+}
+
+/// The current executable, if it is the user-startup binary.
+fn current_user_startup() -> Option<PathBuf> {
+    std::env::current_exe().ok().filter(|exe| {
+        exe.file_stem()
+            .is_some_and(|stem| stem == env!("CARGO_PKG_NAME"))
+    })
+}
+
+/// Systemd user units in `$XDG_CONFIG_HOME/systemd/user`.
+pub struct Systemd {
+    config_dir: PathBuf,
+    search_dirs: Vec<PathBuf>,
+    runner: Arc<dyn CommandRunner>,
+    /// Enable units without `systemctl`, see [`Systemd::offline`].
+    offline: bool,
+    /// The user-startup binary, see [`Systemd::with_exe`].
+    exe: Option<PathBuf>,
+}
+
+impl Systemd {
+    pub fn new() -> Result<Self> {
+        let search_dirs = unit_search_dirs(&home_dir()?, |key| std::env::var(key).ok());
+        Ok(Self {
+            config_dir: search_dirs[0].clone(),
+            search_dirs,
+            runner: Arc::new(ProcessRunner),
+            offline: false,
+            exe: None,
+        })
+    }
+
+    /// Use `config_dir` instead of the default directory. Only `config_dir`
+    /// is searched for units then.
+    ///
+    /// If systemd does not search `config_dir`, e.g. for a staging directory
+    /// of an image or a test sandbox, the backend is [`Systemd::offline`].
+    pub fn with_config_dir(config_dir: impl Into<PathBuf>) -> Self {
+        let config_dir = config_dir.into();
+        Self {
+            search_dirs: vec![config_dir.clone()],
+            offline: !is_searched(&config_dir),
+            config_dir,
+            runner: Arc::new(ProcessRunner),
+            exe: None,
+        }
+    }
+
+    /// Enable and disable units by linking them into `default.target.wants`
+    /// of the config directory, like `systemctl enable` does, without telling
+    /// systemd. Starting, stopping and querying units still runs `systemctl`.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Remove the units of items which run once with `exe remove`, where `exe`
+    /// is the user-startup binary. Defaults to the current executable if it is
+    /// user-startup, so other programs rendering such items must set it.
+    pub fn with_exe(mut self, exe: impl Into<PathBuf>) -> Self {
+        self.exe = Some(exe.into());
+        self
+    }
+
+    /// Run `systemctl` with `runner` instead of spawning processes.
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    fn systemctl(&self, args: &[&str]) -> Result<()> {
+        self.runner
+            .run_checked("systemctl", &[&["--user"], args].concat())?;
+        Ok(())
+    }
+
+    /// The link which enables the unit at `path`.
+    fn wants_link(&self, path: &Path) -> PathBuf {
+        self.config_dir
+            .join("default.target.wants")
+            .join(unit_name(path))
+    }
+}
+
+fn unit_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl StartupBackend for Systemd {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    fn file_ext(&self) -> &'static str {
+        ".service"
+    }
+
+    fn runner(&self) -> &dyn CommandRunner {
+        &*self.runner
+    }
+
+    fn search_dirs(&self) -> Vec<PathBuf> {
+        self.search_dirs.clone()
+    }
+
+    /// The name is the unit name, which systemd only accepts with these
+    /// characters. `@` would make the unit a template.
+    fn validate(&self, item: &StartupItem) -> Result<()> {
+        if !item
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ":_.-".contains(c))
+        {
+            return Err(Error::InvalidName(item.name.clone()));
+        }
+        Ok(())
+    }
+
+    fn render(&self, item: &StartupItem) -> Result<String> {
+        let argv = split_command(&item.command)
+            .filter(|argv| !argv[0].starts_with(EXEC_PREFIXES))
+            .ok_or_else(|| Error::InvalidCommand(item.command.clone()))?;
+        let output = |file: &Option<String>| {
+            file.as_deref().map_or("journal".to_string(), |file| {
+                format!("{}:{}", item.output_mode, file.replace('%', "%%"))
+            })
+        };
+        // A one-shot unit is started when its command exited, so it may take
+        // as long as the command does.
+        let (service_type, start_timeout) = if item.oneshot {
+            ("Type=oneshot\nRemainAfterExit=yes\n", "infinity")
+        } else {
+            ("", "60")
+        };
+        // Only runs if the command succeeded.
+        let exec_start_post = if item.oneshot && item.once {
+            let exe = self
+                .exe
+                .clone()
+                .or_else(current_user_startup)
+                .ok_or(Error::NoExe)?;
+            let remove = [
+                exe.to_string_lossy().into_owned(),
+                "--config-dir".to_string(),
+                self.config_dir.to_string_lossy().into_owned(),
+                "remove".to_string(),
+                file_id(&item.path),
+            ];
+            format!("ExecStartPost={}\n", quote_exec(&remove))
+        } else {
+            String::new()
+        };
+        let mut environment = String::new();
+        if let Some(env_file) = &item.env_file {
+            environment += &format!("EnvironmentFile={}\n", env_file.replace('%', "%%"));
+        }
+        for (key, value) in &item.env {
+            environment += &format!("Environment={}\n", quote_env(key, value));
+        }
+        Ok(format!(
+            r#"{marker}
+{prefixed_cmd}
+[Unit]
+Description={name}
+After=network.target
+{start_limit}
+[Service]
+{service_type}{environment}ExecStart={cmd}
+{exec_start_post}Restart={restart}
+RestartSec={restart_delay}
+LimitNOFILE=4096
+StandardOutput={stdout}
+StandardError={stderr}
+SyslogIdentifier={name}
+LogLevelMax=info
+TimeoutStartSec={start_timeout}
+TimeoutStopSec=30
+WorkingDirectory={cwd}
+
+[Install]
+WantedBy=default.target
+"#,
+            marker = comment(&item.marker.unwrap_or_else(Marker::now).to_string()),
+            prefixed_cmd = comment(&item.command),
+            name = item.name.replace('%', "%%"),
+            cmd = quote_exec(&argv),
+            restart = match item.restart {
+                _ if item.oneshot => "no",
+                Restart::Never => "no",
+                Restart::OnFailure => "on-failure",
+                Restart::Always => "always",
+            },
+            // Every start counts, so the first one is added. The interval
+            // makes the limit last as long as the unit is loaded.
+            start_limit = item.max_restarts.map_or(String::new(), |max| format!(
+                "StartLimitIntervalSec=infinity\nStartLimitBurst={}\n",
+                max.saturating_add(1)
+            )),
+            restart_delay = item.restart_delay.unwrap_or(DEFAULT_RESTART_DELAY),
+            // `~` is the home directory of the user.
+            cwd = item
+                .cwd
+                .as_deref()
+                .map_or("~".to_string(), |cwd| cwd.replace('%', "%%")),
+            stdout = output(&item.stdout),
+            stderr = output(&item.stderr),
+        ))
+    }
+
+    fn parse(&self, path: &Path, content: &str) -> Result<StartupItem> {
+        let (marker, comment, _) = parse_header(content, |line| line.strip_prefix(COMMENT_PREFIX));
+        // Units of other tools often start with a comment too, e.g. a license
+        // header, so the command is only taken from the comment if marked.
+        let command = match marker {
+            Some(_) => comment,
+            None => unit_value(content, "ExecStart").map(str::to_string),
+        }
+        .ok_or_else(|| parse_error(path, "no command found"))?;
+        // Other outputs, e.g. `journal` or `null`, are not files.
+        let output = |key| {
+            unit_value(content, key)?
+                .split_once(':')
+                .and_then(|(mode, file)| Some((mode.parse::<OutputMode>().ok()?, file)))
+        };
+        let mut item = StartupItem::new(command);
+        if let Some(name) = unit_value(content, "Description") {
+            item.name = name.replace("%%", "%");
+        }
+        item.id = file_id(path);
+        item.path = path.to_path_buf();
+        let (stdout, stderr) = (output("StandardOutput"), output("StandardError"));
+        if let Some((mode, _)) = stdout.or(stderr) {
+            item.output_mode = mode;
+        }
+        item.stdout = stdout.map(|(_, file)| file.replace("%%", "%"));
+        item.stderr = stderr.map(|(_, file)| file.replace("%%", "%"));
+        item.env = unit_values(content, "Environment")
+            .flat_map(parse_env)
+            .collect();
+        item.cwd = unit_value(content, "WorkingDirectory")
+            .filter(|cwd| *cwd != "~")
+            .map(|cwd| cwd.trim_start_matches('-').replace("%%", "%"));
+        // A leading `-` makes the file optional.
+        item.env_file = unit_value(content, "EnvironmentFile")
+            .map(|file| file.trim_start_matches('-').replace("%%", "%"));
+        item.restart = unit_value(content, "Restart")
+            .and_then(|restart| restart.parse().ok())
+            .unwrap_or(Restart::Never);
+        item.oneshot = unit_value(content, "Type") == Some("oneshot");
+        item.once =
+            item.oneshot && marker.is_some() && unit_value(content, "ExecStartPost").is_some();
+        item.restart_delay = unit_value(content, "RestartSec").and_then(|sec| sec.parse().ok());
+        item.max_restarts = unit_value(content, "StartLimitBurst")
+            .and_then(|burst| burst.parse::<u32>().ok())
+            .and_then(|burst| burst.checked_sub(1));
+        item.marker = marker;
+        Ok(item)
+    }
+
+    /// Whether the unit is linked into `default.target.wants`, which is what
+    /// `systemctl enable` does for `WantedBy=default.target`.
+    fn is_enabled(&self, path: &Path) -> bool {
+        self.wants_link(path).symlink_metadata().is_ok()
+    }
+
+    /// Write the unit and reload the daemon. The link which enables the unit
+    /// is kept, as the file name does not change.
+    fn update(&self, path: &Path, content: &str) -> Result<()> {
+        self.runner.write_file(path, content)?;
+        if !self.offline
+            && let Err(e) = self.systemctl(&["daemon-reload"])
+        {
+            warn!("{e}");
+        }
+        Ok(())
+    }
+
+    /// Reload the daemon and enable the service.
+    fn enable(&self, path: &Path) -> Result<()> {
+        if self.offline {
+            if self.is_enabled(path) {
+                return Ok(());
+            }
+            // Relative, so the link stays valid if the directory is moved.
+            let original = Path::new("..").join(unit_name(path));
+            return self.runner.symlink(&original, &self.wants_link(path));
+        }
+        // Enabling only needs the unit file, so a user manager which is not
+        // running (e.g. in a container or over ssh) should not stop us.
+        if let Err(e) = self.systemctl(&["daemon-reload"]) {
+            warn!("{e}");
+        }
+        self.systemctl(&["enable", &unit_name(path)])
+    }
+
+    fn disable(&self, path: &Path) -> Result<()> {
+        if self.offline {
+            if !self.is_enabled(path) {
+                return Ok(());
+            }
+            return self.runner.remove_file(&self.wants_link(path));
+        }
+        self.systemctl(&["disable", &unit_name(path)])
+    }
+
+    fn start(&self, path: &Path) -> Result<()> {
+        self.systemctl(&["start", &unit_name(path)])
+    }
+
+    fn stop(&self, path: &Path) -> Result<()> {
+        self.systemctl(&["stop", &unit_name(path)])
+    }
+
+    fn restart(&self, path: &Path) -> Result<()> {
+        self.systemctl(&["restart", &unit_name(path)])
+    }
+
+    /// Units without output files log to the journal.
+    fn logs(&self, item: &StartupItem, options: &LogOptions) -> Result<()> {
+        if item.stdout.is_some() || item.stderr.is_some() {
+            return tail_log_files(item, options);
+        }
+        let unit = unit_name(&item.path);
+        let mut args = vec!["--user", "--unit", &unit, "--no-pager"];
+        let lines = options.lines.map(|lines| lines.to_string());
+        if let Some(lines) = &lines {
+            args.extend(["--lines", lines]);
+        }
+        if let Some(since) = &options.since {
+            args.extend(["--since", since]);
+        }
+        if options.follow {
+            args.push("--follow");
+        }
+        self.runner.run_interactive("journalctl", &args)
+    }
+
+    fn status(&self, path: &Path) -> Result<Status> {
+        let output = self.runner.query_checked(
+            "systemctl",
+            &[
+                "--user",
+                "show",
+                &unit_name(path),
+                &format!("--property={STATUS_PROPERTIES}"),
+            ],
+        )?;
+        Ok(parse_show(&output.stdout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Split a command line of `ExecStart=` into an argv, the reverse of
+    /// [`quote_exec`].
+    fn unquote_exec(line: &str) -> Vec<String> {
+        let mut argv = vec![];
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+            let mut arg = String::new();
+            let quoted = c == '"';
+            let mut next = if quoted { chars.next() } else { Some(c) };
+            while let Some(c) = next {
+                match c {
+                    '"' if quoted => break,
+                    c if c.is_whitespace() && !quoted => break,
+                    '\\' => match chars.next().unwrap() {
+                        'n' => arg.push('\n'),
+                        't' => arg.push('\t'),
+                        c => arg.push(c),
+                    },
+                    c => arg.push(c),
+                }
+                next = chars.next();
+            }
+            argv.push(arg.replace("%%", "%").replace("$$", "$"));
+        }
+        argv
+    }
+
+    #[test]
+    fn test_quote_exec() {
+        let argv = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(quote_exec(&argv(&["foo", "--bar"])), "foo --bar");
+        assert_eq!(
+            quote_exec(&argv(&["echo", "100%", "$HOME", "a b", ""])),
+            r#"echo 100%% $$HOME "a b" """#
+        );
+        assert_eq!(
+            quote_exec(&argv(&["foo", "-bar", r#"say "hi"\"#, ";"])),
+            r#"foo -bar "say \"hi\"\\" \;"#
+        );
+    }
+
+    #[test]
+    fn test_quote_exec_roundtrip() {
+        for command in [
+            "syncthing --no-browser",
+            r#"sh -c 'echo "$HOME" > /tmp/home; date +%s'"#,
+            r#"rclone mount "my drive:" "/mnt/my drive" --vfs-cache-mode\ full"#,
+            r#"python3 -c "print('a\\b\tc')" ; '' '%%' '$$'"#,
+            "exe '-dash' 'tab\tand\nnewline'",
+        ] {
+            let argv = split_command(command).unwrap();
+            assert_eq!(unquote_exec(&quote_exec(&argv)), argv, "command: {command}");
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let item = |name| StartupItem::new("syncthing").name(name);
+        assert!(backend.validate(&item("sync-thing_2.0:x")).is_ok());
+        for name in ["sync thing", "a;b", "$HOME", "sync@", "100%"] {
+            assert!(
+                matches!(backend.validate(&item(name)), Err(Error::InvalidName(_))),
+                "name: {name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_name() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let path = backend.config_dir().join("foo.service");
+        let item = StartupItem::new("foo").name("100%");
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("Description=100%%\n"));
+        assert!(content.contains("SyslogIdentifier=100%%\n"));
+        assert_eq!(backend.parse(&path, &content).unwrap().name, "100%");
+    }
+
+    #[test]
+    fn test_render_exec_start() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let item = StartupItem::new(r#"sh -c 'echo "$HOME"; sleep 5%'"#);
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains(r#"ExecStart=sh -c "echo \"$$HOME\"; sleep 5%%""#));
+        let path = backend.config_dir().join("sh.service");
+        assert_eq!(
+            backend.parse(&path, &content).unwrap().command,
+            item.command
+        );
+    }
+
+    #[test]
+    fn test_render_exec_prefix() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        for command in ["-foo --bar", "'@foo' bar", r#""+foo""#] {
+            assert!(
+                matches!(
+                    backend.render(&StartupItem::new(command)),
+                    Err(Error::InvalidCommand(_))
+                ),
+                "command: {command}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_output() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let path = backend.config_dir().join("syncthing.service");
+        let item = StartupItem::new("syncthing").stdout("/tmp/syncthing.log");
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("StandardOutput=append:/tmp/syncthing.log\n"));
+        assert!(content.contains("StandardError=journal\n"));
+        assert_eq!(backend.parse(&path, &content).unwrap().stdout, item.stdout);
+
+        let item = item
+            .stderr("/tmp/syncthing.err")
+            .output_mode(OutputMode::Truncate);
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("StandardError=truncate:/tmp/syncthing.err\n"));
+        let parsed = backend.parse(&path, &content).unwrap();
+        assert_eq!(parsed.output_mode, OutputMode::Truncate);
+        assert_eq!(parsed.stderr, item.stderr);
+
+        let content = content.replace("truncate:/tmp/syncthing.err", "null");
+        assert_eq!(backend.parse(&path, &content).unwrap().stderr, None);
+
+        let item = StartupItem::new("syncthing").stdout("/tmp/100%h.log");
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("StandardOutput=append:/tmp/100%%h.log\n"));
+        assert_eq!(backend.parse(&path, &content).unwrap().stdout, item.stdout);
+    }
+
+    #[test]
+    fn test_render_env_cwd() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let path = backend.config_dir().join("rclone.service");
+        let item = StartupItem::new("rclone mount drive: /mnt/drive")
+            .env("RCLONE_PASSWORD", r#"100% "secret" \o/"#)
+            .env_file("/home/user/.config/rclone.env");
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains(r#"Environment="RCLONE_PASSWORD=100%% \"secret\" \\o/""#));
+        assert!(content.contains("EnvironmentFile=/home/user/.config/rclone.env\n"));
+        let parsed = backend.parse(&path, &content).unwrap();
+        assert_eq!(parsed.env, item.env);
+        assert_eq!(parsed.env_file, item.env_file);
+        assert!(content.contains("WorkingDirectory=~\n"));
+        assert_eq!(parsed.cwd, None);
+
+        let item = item.cwd("/home/user/100% drive");
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("WorkingDirectory=/home/user/100%% drive\n"));
+        assert_eq!(backend.parse(&path, &content).unwrap().cwd, item.cwd);
+
+        assert_eq!(
+            parse_env(r#"A=1 "B=two words" C='%%'"#),
+            [
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "two words".to_string()),
+                ("C".to_string(), "%".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_restart() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let path = backend.config_dir().join("syncthing.service");
+        let item = StartupItem::new("syncthing");
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("Restart=on-failure\nRestartSec=5\n"));
+        assert!(!content.contains("StartLimitBurst"));
+        assert_eq!(backend.parse(&path, &content).unwrap().max_restarts, None);
+
+        let item = item
+            .restart(Restart::Always)
+            .restart_delay(30)
+            .max_restarts(3);
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("Restart=always\nRestartSec=30\n"));
+        assert!(content.contains("StartLimitIntervalSec=infinity\nStartLimitBurst=4\n"));
+        let parsed = backend.parse(&path, &content).unwrap();
+        assert_eq!(parsed.restart, Restart::Always);
+        assert_eq!(parsed.restart_delay, Some(30));
+        assert_eq!(parsed.max_restarts, Some(3));
+    }
+
+    #[test]
+    fn test_render_oneshot() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let path = backend.config_dir().join("mount.service");
+        let mut item = StartupItem::new("mount-share.sh").oneshot();
+        item.path = path.clone();
+        let content = backend.render(&item).unwrap();
+        assert!(content.contains("[Service]\nType=oneshot\nRemainAfterExit=yes\n"));
+        assert!(content.contains("Restart=no\n"));
+        assert!(content.contains("TimeoutStartSec=infinity\n"));
+        assert!(!content.contains("ExecStartPost"));
+        let parsed = backend.parse(&path, &content).unwrap();
+        assert!(parsed.oneshot && !parsed.once);
+
+        let item = item.once();
+        assert!(matches!(backend.render(&item), Err(Error::NoExe)));
+        let backend = backend.with_exe("/usr/bin/user-startup");
+        let content = backend.render(&item).unwrap();
+        assert_eq!(
+            unit_value(&content, "ExecStartPost"),
+            Some("/usr/bin/user-startup --config-dir /home/user/.config/systemd/user remove mount")
+        );
+        let parsed = backend.parse(&path, &content).unwrap();
+        assert!(parsed.oneshot && parsed.once);
+    }
+
+    #[test]
+    fn test_parse_foreign_unit() {
+        let backend = Systemd::with_config_dir("/home/user/.config/systemd/user");
+        let path = backend.config_dir().join("dbus.service");
+        let content = "# SPDX-License-Identifier: LGPL-2.1-or-later\n\n[Unit]\nDescription=D-Bus \
+                       User Message Bus\n\n[Service]\nExecStart=/usr/bin/dbus-daemon --session\n";
+        let item = backend.parse(&path, content).unwrap();
+        assert!(!item.is_managed());
+        assert_eq!(item.id, "dbus");
+        assert_eq!(item.name, "D-Bus User Message Bus");
+        assert_eq!(item.command, "/usr/bin/dbus-daemon --session");
+    }
+
+    #[test]
+    fn test_unit_search_dirs() {
+        let home = Path::new("/home/user");
+        let dirs = unit_search_dirs(home, |_| None);
+        assert_eq!(dirs[0], Path::new("/home/user/.config/systemd/user"));
+        assert!(dirs.contains(&PathBuf::from("/home/user/.local/share/systemd/user")));
+        assert!(dirs.contains(&PathBuf::from("/etc/systemd/user")));
+        assert!(dirs.contains(&PathBuf::from("/usr/share/systemd/user")));
+        assert_eq!(dirs.last().unwrap(), Path::new("/usr/lib/systemd/user"));
+
+        let dirs = unit_search_dirs(home, |key| match key {
+            "XDG_CONFIG_HOME" => Some("/data/config".to_string()),
+            "XDG_RUNTIME_DIR" => Some("/run/user/1000".to_string()),
+            "XDG_DATA_HOME" => Some("relative/is/ignored".to_string()),
+            _ => None,
+        });
+        assert_eq!(dirs[0], Path::new("/data/config/systemd/user"));
+        assert!(dirs.contains(&PathBuf::from("/run/user/1000/systemd/user")));
+        assert!(dirs.contains(&PathBuf::from("/home/user/.local/share/systemd/user")));
+    }
+
+    #[test]
+    fn test_start_stop_restart() {
+        let runner = Arc::new(crate::runner::RecordingRunner::new());
+        let backend =
+            Systemd::with_config_dir("/home/user/.config/systemd/user").with_runner(runner.clone());
+        let path = backend.config_dir().join("syncthing.service");
+        backend.start(&path).unwrap();
+        backend.stop(&path).unwrap();
+        backend.restart(&path).unwrap();
+        assert_eq!(
+            runner.calls(),
+            [
+                "systemctl --user start syncthing.service",
+                "systemctl --user stop syncthing.service",
+                "systemctl --user restart syncthing.service",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_show() {
+        let status = parse_show(
+            "ActiveState=active\nMainPID=1234\nExecMainStartTimestamp=Sat 2026-10-17 \
+             10:00:00 UTC\nExecMainExitTimestamp=\nExecMainStatus=0\nNRestarts=2\n",
+        );
+        assert_eq!(
+            status,
+            Status {
+                state: State::Active,
+                pid: Some(1234),
+                started: Some("Sat 2026-10-17 10:00:00 UTC".to_string()),
+                last_exit_code: None,
+                restarts: Some(2),
+            }
+        );
+
+        let status = parse_show(
+            "ActiveState=failed\nMainPID=0\nExecMainStartTimestamp=Sat 2026-10-17 10:00:00 \
+             UTC\nExecMainExitTimestamp=Sat 2026-10-17 10:00:01 UTC\nExecMainStatus=3\nNRestarts=0\n",
+        );
+        assert_eq!(status.state, State::Failed);
+        assert_eq!(status.pid, None);
+        assert_eq!(status.last_exit_code, Some(3));
+    }
+
+    #[test]
+    fn test_journal_logs() {
+        let runner = Arc::new(crate::runner::RecordingRunner::new());
+        let backend =
+            Systemd::with_config_dir("/home/user/.config/systemd/user").with_runner(runner.clone());
+        let mut item = StartupItem::new("syncthing");
+        item.path = backend.config_dir().join("syncthing.service");
+        let options = LogOptions {
+            follow: true,
+            lines: Some(20),
+            since: Some("1h ago".to_string()),
+        };
+        backend.logs(&item, &options).unwrap();
+        assert_eq!(
+            runner.calls(),
+            [
+                "journalctl --user --unit syncthing.service --no-pager --lines 20 --since 1h ago \
+              --follow"
+            ]
+        );
+    }
+}
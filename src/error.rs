@@ -0,0 +1,70 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The error type of this library.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// An I/O error on a startup file, the config directory or another file
+    /// of an item.
+    #[error("`{}`: {source}", .path.display())]
+    File { path: PathBuf, source: io::Error },
+    #[error("could not find the home directory")]
+    NoHomeDir,
+    #[error("could not find the user-startup binary, which removes the items run once")]
+    NoExe,
+    #[error("`{command}` failed with {}: {}", exit_code(.code), .stderr.trim())]
+    CommandFailed {
+        command: String,
+        /// The exit code, `None` if the process was killed by a signal.
+        code: Option<i32>,
+        stderr: String,
+    },
+    #[error("startup item `{0}` not found")]
+    NotFound(String),
+    #[error("startup item `{0}` already exists")]
+    AlreadyExists(String),
+    #[error("startup item `{0}` was not created by user-startup")]
+    NotManaged(String),
+    #[error("too many startup items named `{0}`")]
+    NameExhausted(String),
+    #[error("invalid command `{0}`")]
+    InvalidCommand(String),
+    #[error("invalid name `{0}`")]
+    InvalidName(String),
+    #[error("invalid environment variable `{0}`")]
+    InvalidEnv(String),
+    #[error("startup item `{0}` has no log files, add it with --stdout or --stderr")]
+    NoLogs(String),
+    #[error("the {backend} backend cannot {operation} startup items")]
+    Unsupported {
+        backend: &'static str,
+        operation: &'static str,
+    },
+    #[error("invalid manifest: {0}")]
+    Manifest(String),
+    #[error("cannot parse `{}`: {reason}", .path.display())]
+    Parse { path: PathBuf, reason: String },
+}
+
+impl Error {
+    /// Attach `path` to an I/O error on it, for `map_err`.
+    pub fn file(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |source| Self::File {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// The result type of this library.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+fn exit_code(code: &Option<i32>) -> String {
+    code.map_or("no exit code".to_string(), |code| {
+        format!("exit code {code}")
+    })
+}
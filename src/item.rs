@@ -0,0 +1,408 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Result, backend::StartupBackend, extract_name_from_cmd};
+
+/// How long to wait before restarting a command, in seconds, if the item does
+/// not say.
+pub const DEFAULT_RESTART_DELAY: u64 = 5;
+
+/// What the service manager does when the command exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Restart {
+    /// Never restart the command.
+    Never,
+    /// Restart the command when it exits with a non-zero code.
+    #[default]
+    OnFailure,
+    /// Always restart the command.
+    Always,
+}
+
+impl Restart {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Never => "never",
+            Self::OnFailure => "on-failure",
+            Self::Always => "always",
+        }
+    }
+}
+
+impl fmt::Display for Restart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Restart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" | "no" => Ok(Self::Never),
+            "on-failure" => Ok(Self::OnFailure),
+            "always" => Ok(Self::Always),
+            _ => Err(format!("unknown restart policy `{s}`")),
+        }
+    }
+}
+
+/// How the files of `stdout` and `stderr` are opened on every start. launchd
+/// only supports [`OutputMode::Append`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum OutputMode {
+    /// Append to the file.
+    #[default]
+    Append,
+    /// Write from the start of the file, overwriting it in place.
+    File,
+    /// Empty the file first.
+    Truncate,
+}
+
+impl OutputMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Append => "append",
+            Self::File => "file",
+            Self::Truncate => "truncate",
+        }
+    }
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "append" => Ok(Self::Append),
+            "file" => Ok(Self::File),
+            "truncate" => Ok(Self::Truncate),
+            _ => Err(format!("unknown output mode `{s}`")),
+        }
+    }
+}
+
+/// Whether a startup item is running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum State {
+    /// The command is running.
+    Active,
+    /// The command is not running.
+    #[default]
+    Inactive,
+    /// The command exited with an error, and is not restarted.
+    Failed,
+}
+
+impl State {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Inactive => "inactive",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The live state of a startup item, as reported by the service manager.
+/// Fields the service manager does not report are `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Status {
+    pub state: State,
+    /// The pid of the running command.
+    pub pid: Option<u32>,
+    /// When the command was started, formatted by the service manager.
+    pub started: Option<String>,
+    /// The exit code of the last run of the command.
+    pub last_exit_code: Option<i32>,
+    /// How many times the command was restarted.
+    pub restarts: Option<u32>,
+}
+
+/// The version of the format of the startup files written by this crate.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Marks a startup file as created by user-startup. It is rendered as the first
+/// comment of the file, e.g. `user-startup format=1 created=1760745600`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Marker {
+    /// The version of the file format.
+    pub version: u32,
+    /// The creation time of the file, in seconds since the unix epoch.
+    pub created: u64,
+}
+
+impl Marker {
+    const TOOL: &str = "user-startup";
+
+    /// A marker of the current format version created now.
+    pub fn now() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        }
+    }
+
+    /// Parse a marker from a comment line, without the comment prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use user_startup::Marker;
+    /// let marker = Marker::parse("user-startup format=1 created=1760745600").unwrap();
+    /// assert_eq!(marker.created, 1760745600);
+    /// assert_eq!(Marker::parse(&marker.to_string()), Some(marker));
+    /// assert_eq!(Marker::parse("my own unit"), None);
+    /// ```
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split_whitespace();
+        if parts.next()? != Self::TOOL {
+            return None;
+        }
+        let (mut version, mut created) = (None, 0);
+        // Unknown keys are skipped, so that newer formats can add more.
+        for (key, value) in parts.filter_map(|part| part.split_once('=')) {
+            match key {
+                "format" => version = value.parse().ok(),
+                "created" => created = value.parse().unwrap_or_default(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            version: version?,
+            created,
+        })
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} format={} created={}",
+            Self::TOOL,
+            self.version,
+            self.created
+        )
+    }
+}
+
+/// A startup command, as written to or parsed from a startup file.
+///
+/// # Examples
+///
+/// ```rust
+/// use user_startup::{Restart, StartupItem};
+/// let item = StartupItem::new("syncthing --no-browser")
+///     .stdout("/tmp/syncthing.log")
+///     .restart(Restart::Always);
+/// assert_eq!(item.name, "syncthing");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StartupItem {
+    /// The id of the item, which is the file stem of its startup file. Set by
+    /// [`crate::add_item`].
+    pub id: String,
+    /// The name of the item. Defaults to the name of the executable.
+    pub name: String,
+    /// The command to run.
+    pub command: String,
+    /// The file to redirect stdout to.
+    pub stdout: Option<String>,
+    /// The file to redirect stderr to.
+    pub stderr: Option<String>,
+    /// How the files of `stdout` and `stderr` are opened.
+    pub output_mode: OutputMode,
+    /// Environment variables of the command.
+    pub env: BTreeMap<String, String>,
+    /// A file of `KEY=VALUE` lines with more environment variables, which are
+    /// overridden by `env`. systemd reads it whenever the command starts, the
+    /// other backends copy its variables into the startup file.
+    pub env_file: Option<String>,
+    /// The working directory of the command. Defaults to the home directory.
+    pub cwd: Option<String>,
+    pub restart: Restart,
+    /// How long to wait before restarting the command, in seconds. Defaults
+    /// to [`DEFAULT_RESTART_DELAY`].
+    pub restart_delay: Option<u64>,
+    /// How many times the command is restarted at most. Unlimited if `None`.
+    pub max_restarts: Option<u32>,
+    /// The command runs once and exits, e.g. a script mounting a share, so it
+    /// is never restarted. systemd keeps such a unit active after it exits.
+    pub oneshot: bool,
+    /// Remove the item after the first run of the command which succeeds.
+    /// Only used with `oneshot`.
+    pub once: bool,
+    /// Whether the item runs on startup.
+    pub enabled: bool,
+    /// The path of the startup file. Set by [`crate::add_item`].
+    pub path: PathBuf,
+    /// The marker of the startup file, `None` if the file was not created by
+    /// user-startup. Set by [`crate::add_item`].
+    pub marker: Option<Marker>,
+}
+
+impl StartupItem {
+    pub fn new(command: impl Into<String>) -> Self {
+        let command = command.into();
+        Self {
+            id: String::new(),
+            name: extract_name_from_cmd(&command).unwrap_or_default(),
+            command,
+            stdout: None,
+            stderr: None,
+            output_mode: OutputMode::default(),
+            env: BTreeMap::new(),
+            env_file: None,
+            cwd: None,
+            restart: Restart::default(),
+            restart_delay: None,
+            max_restarts: None,
+            oneshot: false,
+            once: false,
+            enabled: true,
+            path: PathBuf::new(),
+            marker: None,
+        }
+    }
+
+    /// Query the live state of the item from the service manager.
+    pub fn status(&self, backend: &dyn StartupBackend) -> Result<Status> {
+        backend.status(&self.path)
+    }
+
+    /// Whether the startup file was created by user-startup.
+    pub fn is_managed(&self) -> bool {
+        self.marker.is_some()
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn stdout(mut self, stdout: impl Into<String>) -> Self {
+        self.stdout = Some(stdout.into());
+        self
+    }
+
+    pub fn stderr(mut self, stderr: impl Into<String>) -> Self {
+        self.stderr = Some(stderr.into());
+        self
+    }
+
+    pub fn output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn env_file(mut self, env_file: impl Into<String>) -> Self {
+        self.env_file = Some(env_file.into());
+        self
+    }
+
+    pub fn cwd(mut self, cwd: impl Into<String>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn restart(mut self, restart: Restart) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    pub fn restart_delay(mut self, seconds: u64) -> Self {
+        self.restart_delay = Some(seconds);
+        self
+    }
+
+    pub fn max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = Some(max_restarts);
+        self
+    }
+
+    /// Make the item a one-shot item, which is never restarted.
+    pub fn oneshot(mut self) -> Self {
+        self.oneshot = true;
+        self.restart = Restart::Never;
+        self
+    }
+
+    /// Make the item a one-shot item, which is removed after its first
+    /// successful run.
+    pub fn once(mut self) -> Self {
+        self.once = true;
+        self.oneshot()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde() {
+        let mut item = StartupItem::new("syncthing --no-browser").stdout("/tmp/syncthing.log");
+        item.id = "syncthing".to_string();
+        item.marker = Some(Marker {
+            version: 1,
+            created: 1760745600,
+        });
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["id"], "syncthing");
+        assert_eq!(json["restart"], "on-failure");
+        assert_eq!(json["stderr"], serde_json::Value::Null);
+        assert_eq!(json["marker"]["created"], 1760745600);
+        assert_eq!(serde_json::from_value::<StartupItem>(json).unwrap(), item);
+
+        let status = Status {
+            state: State::Failed,
+            ..Default::default()
+        };
+        assert_eq!(serde_json::to_value(status).unwrap()["state"], "failed");
+    }
+}
@@ -1,37 +1,29 @@
 #![warn(clippy::cargo)]
 #![allow(clippy::multiple_crate_versions)] // windows-sys
 
+pub mod backend;
+mod error;
+mod item;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod runner;
 pub mod utils;
 use std::{
-    fs,
-    path::{Path, PathBuf},
+    env, fs,
+    path::{self, Path, PathBuf},
     process::Command,
 };
 
-use log::{debug, error, info, warn};
+use backend::{LogOptions, StartupBackend};
+use log::{debug, info, warn};
 
-/// Execute a command and log itself.
-pub fn exec(cmd: &str) -> std::io::Result<()> {
-    let mut parts = cmd.split_whitespace();
-    let true_cmd = parts.next().expect("Command cannot be empty");
-    let args: Vec<&str> = parts.collect();
-    debug!("Executing `{cmd}`");
-    Command::new(true_cmd).args(args).status()?;
-    Ok(())
-}
-
-/// Read the first not-empty line of a file.
-pub fn read_first_line(path: &Path) -> std::io::Result<String> {
-    debug!("Reading first line of `{}`", path.display());
-    let content = fs::read_to_string(path)?;
-    for line in content.lines() {
-        let stripped = line.trim();
-        if !stripped.is_empty() && !stripped.starts_with("#!") {
-            return Ok(stripped.to_string());
-        }
-    }
-    Err(std::io::Error::other("File is empty"))
-}
+pub use crate::{
+    error::{Error, Result},
+    item::{
+        DEFAULT_RESTART_DELAY, FORMAT_VERSION, Marker, OutputMode, Restart, StartupItem, State,
+        Status,
+    },
+};
 
 /// Extract the script name from a command.
 ///
@@ -39,167 +31,357 @@ pub fn read_first_line(path: &Path) -> std::io::Result<String> {
 ///
 /// ```rust
 /// use user_startup::extract_name_from_cmd;
-/// assert_eq!(extract_name_from_cmd("test.cmd"), "test");
-/// assert_eq!(extract_name_from_cmd("D:\\no_install_software\\syncthing\\syncthing.exe"), "syncthing");
+/// assert_eq!(extract_name_from_cmd("test.cmd").unwrap(), "test");
+/// assert_eq!(extract_name_from_cmd("D:\\no_install_software\\syncthing\\syncthing.exe").unwrap(), "syncthing");
+/// assert!(extract_name_from_cmd("  ").is_err());
 /// ```
-pub fn extract_name_from_cmd(cmd: &str) -> String {
+pub fn extract_name_from_cmd(cmd: &str) -> Result<String> {
     cmd.split_whitespace()
         .find(|x| !x.is_empty())
-        .expect("Command is empty!")
-        .split(['/', '\\'])
-        .rev()
-        .find(|x| !x.is_empty())
-        .expect("cannot extract name from invalid command")
-        .split('.')
-        .find(|x| !x.is_empty())
-        .expect("cannot extract name from invalid command")
-        .into()
+        .and_then(|x| x.split(['/', '\\']).rev().find(|x| !x.is_empty()))
+        .and_then(|x| x.split('.').find(|x| !x.is_empty()))
+        .map(Into::into)
+        .ok_or_else(|| Error::InvalidCommand(cmd.to_string()))
 }
 
 /// Find a writable path for a startup command. Because the command may be not
 /// unique, it will try to find the first available filename like this:
 ///
 /// test, test1, test2, test3, test4, test5 ... test1000.
-pub fn find_writable_path(name: impl AsRef<str>) -> PathBuf {
+///
+/// An id is taken if it exists in any search directory of the backend, so a new
+/// item never shadows an existing one.
+pub fn find_writable_path(backend: &dyn StartupBackend, name: impl AsRef<str>) -> Result<PathBuf> {
     let name = name.as_ref();
     debug!("Finding writable path for `{name}`");
-    let base_path = &utils::CONFIG_PATH;
-    let ext = utils::FILE_EXT;
+    let base_path = backend.config_dir();
+    let ext = backend.file_ext();
 
-    let initial_path = base_path.join(format!("{name}{ext}"));
-    if !initial_path.exists() {
-        return initial_path;
+    if backend.locate(name).is_none() {
+        return Ok(base_path.join(format!("{name}{ext}")));
     }
 
     for i in 1..1000 {
-        let path = base_path.join(format!("{name}{i}{ext}"));
-        if !path.exists() {
+        let id = format!("{name}{i}");
+        if backend.locate(&id).is_none() {
+            let path = base_path.join(format!("{id}{ext}"));
             debug!(
                 "Found writable path `{}`",
                 path.file_name().unwrap_or_default().to_string_lossy()
             );
-            return path;
+            return Ok(path);
         }
     }
-    panic!("TOO MANY ITEMS OF SAME NAME!");
+    Err(Error::NameExhausted(name.to_string()))
 }
 
-/// Add a new startup command.
-pub fn add_item(cmd: &str, name: Option<&str>, stdout: Option<&str>, stderr: Option<&str>) {
-    if cfg!(target_os = "linux") && (stdout.is_some() || stderr.is_some()) {
-        warn!("--stdout and --stderr are not supported for linux startup scripts");
+/// Check that an item can be rendered into a startup file.
+pub(crate) fn validate(item: &StartupItem) -> Result<()> {
+    // The command is kept in a comment line of the startup file.
+    if item.command.trim().is_empty() || item.command.contains('\n') {
+        return Err(Error::InvalidCommand(item.command.clone()));
+    }
+    // The name is the file name of the startup file, and is rendered into
+    // lines of it.
+    if item.name.is_empty()
+        || item.name.contains(['/', '\\'])
+        || item.name.contains(char::is_control)
+    {
+        return Err(Error::InvalidName(item.name.clone()));
+    }
+    // Every variable is rendered on a line of its own.
+    for (key, value) in &item.env {
+        if key.is_empty() || key.contains(['=', '\n', '\0']) || value.contains(['\n', '\0']) {
+            return Err(Error::InvalidEnv(key.clone()));
+        }
     }
+    Ok(())
+}
 
-    let path = if let Some(name) = name {
-        find_writable_path(name)
-    } else {
-        find_writable_path(extract_name_from_cmd(cmd))
-    };
+/// Make the files and the working directory of an item absolute, as the
+/// command does not run in the current directory.
+pub(crate) fn resolve_paths(item: &mut StartupItem) -> Result<()> {
+    for path in [
+        &mut item.stdout,
+        &mut item.stderr,
+        &mut item.env_file,
+        &mut item.cwd,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *path = path::absolute(&*path)?.to_string_lossy().into_owned();
+    }
+    Ok(())
+}
 
-    fs::write(&path, utils::format(cmd, None, stdout, stderr))
-        .expect("Failed to write config file");
+/// Create the directories of the output files, as service managers only
+/// create the files.
+fn create_output_dirs(backend: &dyn StartupBackend, item: &StartupItem) -> Result<()> {
+    for output in [&item.stdout, &item.stderr].into_iter().flatten() {
+        if let Some(dir) = Path::new(output).parent() {
+            backend.runner().create_dir_all(dir)?;
+        }
+    }
+    Ok(())
+}
 
-    info!("Added `{}` to `{}`", cmd, path.display());
+/// Add a new startup item, and return it with its id and path filled in.
+/// Relative paths are resolved against the current directory.
+pub fn add_item(backend: &dyn StartupBackend, mut item: StartupItem) -> Result<StartupItem> {
+    validate(&item)?;
+    backend.validate(&item)?;
+    resolve_paths(&mut item)?;
+    let path = find_writable_path(backend, &item.name)?;
+    item.id = backend::file_id(&path);
+    item.path = path;
+    item.marker = Some(Marker::now());
 
-    // Reload the daemon and enable the service
-    #[cfg(target_os = "linux")]
-    {
-        exec("systemctl daemon-reload --user").expect("daemon reloading error");
-        exec(
-            format!(
-                "systemctl enable {} --user",
-                path.file_name().unwrap().to_string_lossy()
-            )
-            .as_str(),
-        )
-        .expect("daemon enabling error");
+    let content = backend.render(&item)?;
+    create_output_dirs(backend, &item)?;
+    if let Err(e) = backend.install(&item.path, &content) {
+        // Keep the id free for the next try.
+        if item.path.exists()
+            && let Err(e) = backend.runner().remove_file(&item.path)
+        {
+            warn!("Failed to remove `{}`: {e}", item.path.display());
+        }
+        return Err(e);
     }
+
+    info!("Added `{}` to `{}`", item.command, item.path.display());
+    Ok(item)
 }
 
-/// Get a list of startup commands.
+/// Change a startup item created by user-startup in place, and return it. The
+/// id, the path, the marker and whether it is enabled are kept.
 ///
-/// # Returns
-///
-/// A vector of tuples, where the first element is the id of the command and the
-/// second element is the command itself.
-pub fn get_items_list() -> Vec<(String, String)> {
-    let config_path = utils::CONFIG_PATH.as_os_str();
-    debug!(
-        "Finding config files in `{}` with extension `{}`",
-        config_path.to_string_lossy(),
-        utils::FILE_EXT
-    );
-    let mut res = vec![];
-
-    for entry in fs::read_dir(config_path)
-        .expect("Failed to read config directory")
-        .flatten()
-    {
-        let path = entry.path();
-        if path
-            .extension()
-            .is_some_and(|ext| ext == utils::FILE_EXT.trim_start_matches('.'))
-            && let Ok(first_line) = read_first_line(&path)
-        {
-            let id = path.file_stem().unwrap().to_string_lossy().into_owned();
-            if first_line.starts_with(utils::COMMENT_PREFIX) {
-                let command = first_line.trim_start_matches(utils::COMMENT_PREFIX).trim();
-                res.push((id, command.to_string()));
+/// The change takes effect the next time the command starts, or now for a
+/// loaded launchd agent, which is reloaded.
+pub fn edit_item(
+    backend: &dyn StartupBackend,
+    id: &str,
+    edit: impl FnOnce(&mut StartupItem),
+) -> Result<StartupItem> {
+    let old = read_managed(backend, id)?;
+    let mut item = old.clone();
+    edit(&mut item);
+    validate(&item)?;
+    backend.validate(&item)?;
+    resolve_paths(&mut item)?;
+    (item.id, item.path, item.marker, item.enabled) = (old.id, old.path, old.marker, old.enabled);
+
+    let content = backend.render(&item)?;
+    create_output_dirs(backend, &item)?;
+    backend.update(&item.path, &content)?;
+    info!("Updated `{}`", item.path.display());
+    Ok(item)
+}
+
+/// Edit the startup file of an item in `$VISUAL` or `$EDITOR`, and return the
+/// item. The file is edited in a copy, which only replaces the startup file
+/// if it can still be parsed and keeps its marker. Empty variables are
+/// ignored.
+pub fn edit_item_file(backend: &dyn StartupBackend, id: &str) -> Result<StartupItem> {
+    let StartupItem { path, marker, .. } = read_managed(backend, id)?;
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| utils::DEFAULT_EDITOR.to_string());
+    let argv = utils::split_command(&editor).ok_or(Error::InvalidCommand(editor))?;
+
+    // A new file only readable by the user, as the temporary directory may be
+    // shared. It keeps the file name, so editors recognize the format.
+    let copy = tempfile::Builder::new()
+        .prefix("user-startup-")
+        .suffix(&format!(
+            "-{}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ))
+        .tempfile()?
+        .into_temp_path();
+    fs::write(&copy, fs::read(&path).map_err(Error::file(&path))?).map_err(Error::file(&copy))?;
+    debug!("Editing `{}` with `{}`", copy.display(), argv.join(" "));
+    let edited = Command::new(&argv[0])
+        .args(&argv[1..])
+        .arg(&copy)
+        .status()
+        .map_err(Error::from)
+        .and_then(|status| {
+            if !status.success() {
+                return Err(Error::CommandFailed {
+                    command: argv.join(" "),
+                    code: status.code(),
+                    stderr: String::new(),
+                });
             }
-        }
+            let content = fs::read_to_string(&copy).map_err(Error::file(&copy))?;
+            let item = backend.parse(&path, &content)?;
+            // Without its marker, the file would no longer be managed.
+            if item.marker != marker {
+                return Err(Error::Parse {
+                    path: path.clone(),
+                    reason: "the user-startup marker was removed or changed".to_string(),
+                });
+            }
+            Ok((item, content))
+        });
+    copy.close()?;
+
+    let (mut item, content) = edited?;
+    backend.update(&path, &content)?;
+    item.enabled = backend.is_enabled(&path);
+    info!("Updated `{}`", path.display());
+    Ok(item)
+}
+
+/// Get a list of startup items created by user-startup.
+pub fn get_items_list(backend: &dyn StartupBackend) -> Result<Vec<StartupItem>> {
+    let mut items = backend.list()?;
+    items.retain(StartupItem::is_managed);
+    Ok(items)
+}
+
+/// Get a list of all startup items, including the ones not created by
+/// user-startup.
+pub fn get_all_items(backend: &dyn StartupBackend) -> Result<Vec<StartupItem>> {
+    backend.list()
+}
+
+/// Read an item by id, returning [`Error::NotManaged`] if it was not created
+/// by user-startup.
+fn read_managed(backend: &dyn StartupBackend, id: &str) -> Result<StartupItem> {
+    match backend.read(&locate_item(backend, id)?) {
+        Ok(item) if item.is_managed() => Ok(item),
+        Ok(_) | Err(Error::Parse { .. }) => Err(Error::NotManaged(id.to_string())),
+        Err(e) => Err(e),
     }
-    res
 }
 
-/// Remove startup commands.
-pub fn remove_items(ids: Vec<String>) {
+/// Find the startup file of an item by id.
+fn locate_item(backend: &dyn StartupBackend, id: &str) -> Result<PathBuf> {
+    backend
+        .locate(id)
+        .ok_or_else(|| Error::NotFound(id.to_string()))
+}
+
+/// Get a startup item by id.
+pub fn get_item(backend: &dyn StartupBackend, id: &str) -> Result<StartupItem> {
+    backend.read(&locate_item(backend, id)?)
+}
+
+/// Print the output of a startup command.
+pub fn show_logs(backend: &dyn StartupBackend, id: &str, options: &LogOptions) -> Result<()> {
+    backend.logs(&get_item(backend, id)?, options)
+}
+
+/// Remove startup commands. Stops at the first id which cannot be removed.
+///
+/// Files not created by user-startup are only removed with `force`.
+pub fn remove_items(backend: &dyn StartupBackend, ids: Vec<String>, force: bool) -> Result<()> {
     for id in ids {
-        let path = utils::CONFIG_PATH.join(format!("{}{}", id, utils::FILE_EXT));
-        if path.exists() {
-            // Disable the service
-            #[cfg(target_os = "linux")]
-            {
-                exec(
-                    format!(
-                        "systemctl disable {} --user",
-                        path.file_name().unwrap().to_string_lossy()
-                    )
-                    .as_str(),
-                )
-                .expect("daemon disabling error");
-            }
-            fs::remove_file(&path)
-                .unwrap_or_else(|e| panic!("Failed to remove file `{}`: {}", path.display(), e));
-            info!("Removed id `{id}`");
-        } else {
-            error!("Config file id `{id}` not found");
+        let path = locate_item(backend, &id)?;
+        if !force {
+            read_managed(backend, &id)?;
         }
+        backend.uninstall(&path)?;
+        info!("Removed id `{id}` from `{}`", path.display());
+    }
+    Ok(())
+}
+
+/// Make startup commands run on startup again. Stops at the first id which
+/// cannot be enabled.
+pub fn enable_items(backend: &dyn StartupBackend, ids: Vec<String>) -> Result<()> {
+    for id in ids {
+        backend.enable(&locate_item(backend, &id)?)?;
+        info!("Enabled id `{id}`");
+    }
+    Ok(())
+}
+
+/// Stop startup commands from running on startup, keeping their startup
+/// files. Stops at the first id which cannot be disabled.
+pub fn disable_items(backend: &dyn StartupBackend, ids: Vec<String>) -> Result<()> {
+    for id in ids {
+        backend.disable(&locate_item(backend, &id)?)?;
+        info!("Disabled id `{id}`");
+    }
+    Ok(())
+}
+
+/// Start startup commands now. Stops at the first id which cannot be started.
+pub fn start_items(backend: &dyn StartupBackend, ids: Vec<String>) -> Result<()> {
+    for id in ids {
+        backend.start(&locate_item(backend, &id)?)?;
+        info!("Started id `{id}`");
     }
+    Ok(())
+}
+
+/// Stop running startup commands. Stops at the first id which cannot be
+/// stopped.
+pub fn stop_items(backend: &dyn StartupBackend, ids: Vec<String>) -> Result<()> {
+    for id in ids {
+        backend.stop(&locate_item(backend, &id)?)?;
+        info!("Stopped id `{id}`");
+    }
+    Ok(())
+}
+
+/// Restart startup commands. Stops at the first id which cannot be restarted.
+pub fn restart_items(backend: &dyn StartupBackend, ids: Vec<String>) -> Result<()> {
+    for id in ids {
+        backend.restart(&locate_item(backend, &id)?)?;
+        info!("Restarted id `{id}`");
+    }
+    Ok(())
 }
 
 /// Open the startup folder.
-pub fn open_config_folder() {
+pub fn open_config_folder(backend: &dyn StartupBackend) -> Result<()> {
     Command::new(utils::OPEN_COMMAND)
-        .arg(utils::CONFIG_PATH.as_os_str())
-        .spawn()
-        .expect("Failed to open config folder")
-        .wait()
-        .expect("Failed to open config folder");
+        .arg(backend.config_dir())
+        .spawn()?
+        .wait()?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    #[cfg(windows)]
     use super::*;
 
+    #[test]
+    fn test_validate() {
+        // Backends reject more names themselves, e.g. systemd spaces.
+        assert!(validate(&StartupItem::new("syncthing").name("sync thing")).is_ok());
+        for name in [
+            "",
+            "../../etc/evil",
+            r"..\evil",
+            "evil\nExecStartPre=rm",
+            "tab\t",
+        ] {
+            assert!(
+                matches!(
+                    validate(&StartupItem::new("syncthing").name(name)),
+                    Err(Error::InvalidName(_))
+                ),
+                "name: {name:?}"
+            );
+        }
+    }
+
     #[test]
     #[cfg(windows)]
     fn test_find_writable_path() {
-        let path = find_writable_path("test");
-        assert_eq!(path, utils::CONFIG_PATH.join("test.cmd"));
-        add_item("test", None, None, None);
-        let path = find_writable_path("test");
-        assert_eq!(path, utils::CONFIG_PATH.join("test1.cmd"));
-        remove_items(vec!["test".to_string()]);
+        let backend = backend::StartupFolder::new().unwrap();
+        let path = find_writable_path(&backend, "test").unwrap();
+        assert_eq!(path, backend.config_dir().join("test.cmd"));
+        add_item(&backend, StartupItem::new("test")).unwrap();
+        let path = find_writable_path(&backend, "test").unwrap();
+        assert_eq!(path, backend.config_dir().join("test1.cmd"));
+        remove_items(&backend, vec!["test".to_string()], false).unwrap();
     }
 }
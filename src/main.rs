@@ -1,18 +1,40 @@
-use std::fs;
-#[cfg(target_os = "windows")]
-use std::path::PathBuf;
+use std::{env, fs, io, path::PathBuf, sync::Arc};
 
-use clap::{Parser, Subcommand, ValueHint};
-use log::{LevelFilter, warn};
+use clap::{Parser, Subcommand, ValueEnum, ValueHint};
+use log::{LevelFilter, error, info, warn};
+use serde::Serialize;
 #[cfg(target_os = "windows")]
-use user_startup::utils::run_no_window;
-use user_startup::{add_item, get_items_list, open_config_folder, remove_items, utils};
+use user_startup::utils::{self, RunOptions};
+use user_startup::{
+    DEFAULT_RESTART_DELAY, Error, OutputMode, Restart, Result, StartupItem, Status, add_item,
+    backend,
+    backend::{LogOptions, StartupBackend},
+    disable_items, edit_item, edit_item_file, enable_items, get_all_items, get_item,
+    get_items_list,
+    manifest::{self, Manifest, OnConflict},
+    open_config_folder, remove_items, restart_items,
+    runner::{DryRunRunner, ProcessRunner},
+    show_logs, start_items, stop_items,
+};
 
 #[derive(Parser)]
 #[command(about = "Make any command automatically run on startup")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Store the startup files in this directory instead of the default one
+    /// of the platform.
+    #[arg(
+        long,
+        global = true,
+        env = "USER_STARTUP_CONFIG_DIR",
+        value_hint(ValueHint::DirPath)
+    )]
+    config_dir: Option<PathBuf>,
+    /// Print the files which would be written and the commands which would
+    /// run, without changing anything.
+    #[arg(long, global = true)]
+    dry_run: bool,
 }
 
 #[derive(Subcommand)]
@@ -23,7 +45,8 @@ enum Commands {
         /// The command to add. Please wrap the command in quotes ('').
         command: String,
         /// The name of the command. If not provided, the first word of the
-        /// command will be used.
+        /// command will be used. systemd only accepts ASCII letters, digits
+        /// and `:_.-` in it.
         #[arg(short, long)]
         name: Option<String>,
         /// Redirect the command's stdout to a file.
@@ -32,14 +55,98 @@ enum Commands {
         /// Redirect the command's stderr to a file.
         #[arg(long, value_hint(ValueHint::FilePath))]
         stderr: Option<String>,
+        /// How the files of `--stdout` and `--stderr` are opened.
+        #[arg(long, value_enum, default_value_t)]
+        output_mode: OutputModeArg,
+        /// Set an environment variable of the command. Can be repeated.
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env)]
+        env: Vec<(String, String)>,
+        /// Read more environment variables from a file of `KEY=VALUE` lines.
+        #[arg(long, value_hint(ValueHint::FilePath))]
+        env_file: Option<String>,
+        /// The working directory of the command. Defaults to the current
+        /// directory, or the home directory if it cannot be read.
+        #[arg(long, value_hint(ValueHint::DirPath))]
+        cwd: Option<String>,
+        /// When to restart the command after it exits.
+        #[arg(long, value_enum, default_value_t)]
+        restart: RestartArg,
+        /// How long to wait before restarting the command, in seconds.
+        /// Defaults to 5.
+        #[arg(long, value_name = "SECONDS")]
+        restart_delay: Option<u64>,
+        /// Restart the command at most this many times. Not supported by
+        /// launchd.
+        #[arg(long)]
+        max_restarts: Option<u32>,
+        /// The command runs once and exits, e.g. a script mounting a share,
+        /// so it is never restarted.
+        #[arg(long, conflicts_with_all = ["restart", "restart_delay", "max_restarts"])]
+        oneshot: bool,
+        /// Remove the startup command after its first successful run.
+        /// Implies `--oneshot`.
+        #[arg(long, conflicts_with_all = ["restart", "restart_delay", "max_restarts"])]
+        once: bool,
+        /// Also start the command now.
+        #[arg(long)]
+        now: bool,
+    },
+    /// Change a startup command in place, keeping its id. Without flags,
+    /// the startup file is opened in `$VISUAL` or `$EDITOR`.
+    #[command(visible_alias = "e")]
+    Edit {
+        /// The id of the item to edit
+        id: String,
+        /// The new command.
+        #[arg(long)]
+        command: Option<String>,
+        /// The new name of the command.
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Redirect the command's stdout to this file instead.
+        #[arg(long, value_hint(ValueHint::FilePath))]
+        stdout: Option<String>,
+        /// Redirect the command's stderr to this file instead.
+        #[arg(long, value_hint(ValueHint::FilePath))]
+        stderr: Option<String>,
+        /// Open the files of `--stdout` and `--stderr` like this instead.
+        #[arg(long, value_enum)]
+        output_mode: Option<OutputModeArg>,
     },
     /// List all startup commands
     #[command(visible_alias = "l", visible_alias = "info", visible_alias = "i")]
     List {
-        /// Do not use a table to display the list. Instead, use `\t` to
-        /// separate the id and command.
-        #[arg(long)]
+        /// Print only the ids and the commands, as tab separated values with a
+        /// header. `--format tsv` prints every field.
+        #[arg(long, conflicts_with_all = ["format", "status"])]
         no_table: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+        /// Also list startup files which were not created by user-startup.
+        #[arg(short, long)]
+        all: bool,
+        /// Also show whether the commands are running.
+        #[arg(short, long)]
+        status: bool,
+    },
+    /// Show the startup file and the parsed fields of a startup command
+    Show {
+        /// The id of the item
+        id: String,
+        /// Also print the content of the startup file, with `--format
+        /// table`.
+        #[arg(short, long)]
+        raw: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+    /// Show whether startup commands are running
+    #[command(visible_alias = "s")]
+    Status {
+        /// The ids of the items. All startup commands if empty.
+        ids: Vec<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
     },
     /// Remove startup commands
     #[command(visible_alias = "r", visible_alias = "rm")]
@@ -47,6 +154,77 @@ enum Commands {
         /// The ids of the items to remove
         #[clap(required = true)]
         ids: Vec<String>,
+        /// Also remove startup files which were not created by user-startup.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Make startup commands run on startup again
+    Enable {
+        /// The ids of the items to enable
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+    /// Stop startup commands from running on startup, without removing them
+    Disable {
+        /// The ids of the items to disable
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+    /// Start startup commands now
+    Start {
+        /// The ids of the items to start
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+    /// Stop running startup commands
+    Stop {
+        /// The ids of the items to stop
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+    /// Restart startup commands
+    Restart {
+        /// The ids of the items to restart
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+    /// Show the output of a startup command
+    Logs {
+        /// The id of the item
+        id: String,
+        /// Keep showing new output
+        #[arg(short, long)]
+        follow: bool,
+        /// Only show the last lines
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
+        /// Only show output since this time, e.g. `2026-10-18 10:00` or `1h
+        /// ago`. Only supported by the systemd journal.
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Make the startup commands match a TOML manifest. Prints the changes
+    /// first.
+    Apply {
+        /// The manifest file
+        #[arg(value_hint(ValueHint::FilePath))]
+        manifest: PathBuf,
+        /// Also remove startup commands which are not in the manifest.
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Print the startup commands as a manifest, e.g. `user-startup export >
+    /// items.toml`
+    Export,
+    /// Add the startup commands of a manifest, e.g. exported on another
+    /// machine
+    Import {
+        /// The manifest file
+        #[arg(value_hint(ValueHint::FilePath))]
+        manifest: PathBuf,
+        /// What to do with a startup command whose id is taken.
+        #[arg(long, value_enum, default_value_t)]
+        on_conflict: OnConflictArg,
     },
     /// Open the startup folder
     #[command(visible_alias = "o")]
@@ -63,19 +241,141 @@ enum Commands {
         /// Redirect the command's stderr to a file.
         #[arg(long, value_hint(ValueHint::FilePath))]
         stderr: Option<PathBuf>,
+        /// How the files of `--stdout` and `--stderr` are opened.
+        #[arg(long, value_enum, default_value_t)]
+        output_mode: OutputModeArg,
+        /// Run the command in this directory.
+        #[arg(long, value_hint(ValueHint::DirPath))]
+        cwd: Option<PathBuf>,
+        /// When to restart the command after it exits.
+        #[arg(long, value_enum, default_value = "never")]
+        restart: RestartArg,
+        /// How long to wait before restarting the command, in seconds.
+        #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_RESTART_DELAY)]
+        restart_delay: u64,
+        /// Restart the command at most this many times.
+        #[arg(long)]
+        max_restarts: Option<u32>,
+        /// Remove this startup script once the command succeeded.
+        #[arg(long, value_hint(ValueHint::FilePath))]
+        remove_on_success: Option<PathBuf>,
+        /// Run and restart the command in this process, instead of in a
+        /// process without a window.
+        #[arg(long, hide = true)]
+        supervise: bool,
     },
 }
 
+/// The output format of `list`, `show` and `status`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// For humans.
+    #[default]
+    Table,
+    /// Tab separated values with a header, escaping tabs, newlines and
+    /// backslashes with a backslash.
+    Tsv,
+    Json,
+    Yaml,
+}
+
+/// [`OnConflict`] on the command line.
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum OnConflictArg {
+    /// Import nothing.
+    #[default]
+    Error,
+    /// Skip the startup command.
+    Skip,
+    /// Add the startup command with the next free id, e.g. `syncthing1`.
+    Rename,
+}
+
+/// [`Restart`] on the command line.
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum RestartArg {
+    /// Never restart the command.
+    Never,
+    /// Restart the command when it exits with a non-zero code.
+    #[default]
+    OnFailure,
+    /// Always restart the command.
+    Always,
+}
+
+impl From<RestartArg> for Restart {
+    fn from(restart: RestartArg) -> Self {
+        match restart {
+            RestartArg::Never => Self::Never,
+            RestartArg::OnFailure => Self::OnFailure,
+            RestartArg::Always => Self::Always,
+        }
+    }
+}
+
+/// [`OutputMode`] on the command line.
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum OutputModeArg {
+    /// Append to the files.
+    #[default]
+    Append,
+    /// Write from the start of the files, overwriting them in place. Not
+    /// supported by launchd.
+    File,
+    /// Empty the files first. Not supported by launchd.
+    Truncate,
+}
+
+impl From<OutputModeArg> for OutputMode {
+    fn from(mode: OutputModeArg) -> Self {
+        match mode {
+            OutputModeArg::Append => Self::Append,
+            OutputModeArg::File => Self::File,
+            OutputModeArg::Truncate => Self::Truncate,
+        }
+    }
+}
+
+/// An item with its status, as serialized by `--format json|yaml`.
+#[derive(Serialize)]
+struct Entry<'a> {
+    #[serde(flatten)]
+    item: &'a StartupItem,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<&'a Status>,
+}
+
 fn main() {
     log_init();
 
-    let config_path = &utils::CONFIG_PATH;
-    if !config_path.exists() {
-        warn!("Config path not found. Creating it...");
-        fs::create_dir_all(config_path.as_os_str()).expect("Failed to create config directory");
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        error!("{e}");
+        std::process::exit(1);
     }
+}
 
-    let cli = Cli::parse();
+fn run(cli: Cli) -> Result<()> {
+    let backend = if cli.dry_run {
+        backend::native_with_runner(
+            cli.config_dir,
+            Arc::new(DryRunRunner::new(Arc::new(ProcessRunner), io::stdout())),
+        )?
+    } else {
+        match cli.config_dir {
+            Some(config_dir) => backend::native_with_config_dir(config_dir),
+            None => backend::native()?,
+        }
+    };
+    let config_path = backend.config_dir();
+    if !config_path.exists() {
+        if cli.dry_run {
+            println!("Would create `{}`", config_path.display());
+        } else {
+            warn!("Config path not found. Creating it...");
+            fs::create_dir_all(config_path).map_err(Error::file(config_path))?;
+        }
+    }
 
     match cli.command {
         Commands::Add {
@@ -83,34 +383,411 @@ fn main() {
             name,
             stdout,
             stderr,
-        } => add_item(
-            &command,
-            name.as_deref(),
-            stdout.as_deref(),
-            stderr.as_deref(),
-        ),
-        Commands::List { no_table } => {
+            output_mode,
+            env,
+            env_file,
+            cwd,
+            restart,
+            restart_delay,
+            max_restarts,
+            oneshot,
+            once,
+            now,
+        } => {
+            let mut item = StartupItem::new(command);
+            if let Some(name) = name {
+                item = item.name(name);
+            }
+            item.stdout = stdout;
+            item.stderr = stderr;
+            item.output_mode = output_mode.into();
+            item.env = env.into_iter().collect();
+            item.env_file = env_file;
+            item.cwd = cwd.or_else(|| {
+                env::current_dir()
+                    .ok()
+                    .map(|dir| dir.to_string_lossy().into_owned())
+            });
+            item.restart = restart.into();
+            item.restart_delay = restart_delay;
+            item.max_restarts = max_restarts;
+            if once {
+                item = item.once();
+            } else if oneshot {
+                item = item.oneshot();
+            }
+            let item = add_item(&*backend, item)?;
+            if now {
+                // Not looked up by id, as a dry run does not write the file.
+                backend.start(&item.path)?;
+                info!("Started id `{}`", item.id);
+            }
+        }
+        Commands::Edit {
+            id,
+            command,
+            name,
+            stdout,
+            stderr,
+            output_mode,
+        } => {
+            if command.is_none()
+                && name.is_none()
+                && stdout.is_none()
+                && stderr.is_none()
+                && output_mode.is_none()
+            {
+                edit_item_file(&*backend, &id)?;
+            } else {
+                edit_item(&*backend, &id, |item| {
+                    if let Some(command) = command {
+                        item.command = command;
+                    }
+                    if let Some(name) = name {
+                        item.name = name;
+                    }
+                    if stdout.is_some() {
+                        item.stdout = stdout;
+                    }
+                    if stderr.is_some() {
+                        item.stderr = stderr;
+                    }
+                    if let Some(output_mode) = output_mode {
+                        item.output_mode = output_mode.into();
+                    }
+                })?;
+            }
+        }
+        Commands::List {
+            no_table,
+            format,
+            all,
+            status,
+        } => {
+            let items = if all {
+                get_all_items(&*backend)?
+            } else {
+                get_items_list(&*backend)?
+            };
+            let statuses = if status {
+                Some(query_statuses(&*backend, &items))
+            } else if !no_table && format == Format::Table && items.iter().any(|item| item.oneshot)
+            {
+                // The last exit code tells whether a one-shot command
+                // succeeded, so the table shows it if the backend knows it.
+                let statuses = items
+                    .iter()
+                    .map(|item| item.status(&*backend).ok())
+                    .collect::<Vec<_>>();
+                statuses.iter().any(Option::is_some).then_some(statuses)
+            } else {
+                None
+            };
             if no_table {
-                let temp = get_items_list().into_iter();
                 println!("id\tcommand");
-                temp.for_each(|(id, c)| println!("{id}\t{c}"));
+                for item in &items {
+                    println!("{}\t{}", tsv_field(&item.id), tsv_field(&item.command));
+                }
+            } else if format == Format::Table {
+                list_items(&items, all, statuses.as_deref());
+            } else {
+                print_entries(&items, statuses.as_deref(), format)?;
+            }
+        }
+        Commands::Show { id, raw, format } => {
+            let item = get_item(&*backend, &id)?;
+            if format == Format::Table {
+                print_item(&item);
+                if raw {
+                    println!();
+                    print!(
+                        "{}",
+                        fs::read_to_string(&item.path).map_err(Error::file(&item.path))?
+                    );
+                }
+            } else if format == Format::Tsv {
+                print_entries(&[item], None, format)?;
+            } else {
+                print_serialized(&item, format)?;
+            }
+        }
+        Commands::Status { ids, format } => {
+            let items = if ids.is_empty() {
+                get_items_list(&*backend)?
+            } else {
+                ids.iter()
+                    .map(|id| get_item(&*backend, id))
+                    .collect::<Result<Vec<_>>>()?
+            };
+            let statuses = items
+                .iter()
+                .map(|item| item.status(&*backend).map(Some))
+                .collect::<Result<Vec<_>>>()?;
+            if format == Format::Table {
+                for (item, status) in items.iter().zip(statuses.iter().flatten()) {
+                    print_status(item, status);
+                }
             } else {
-                list_items()
+                print_entries(&items, Some(&statuses), format)?;
             }
         }
-        Commands::Remove { ids } => remove_items(ids),
-        Commands::Open => open_config_folder(),
+        Commands::Remove { ids, force } => remove_items(&*backend, ids, force)?,
+        Commands::Enable { ids } => enable_items(&*backend, ids)?,
+        Commands::Disable { ids } => disable_items(&*backend, ids)?,
+        Commands::Start { ids } => start_items(&*backend, ids)?,
+        Commands::Stop { ids } => stop_items(&*backend, ids)?,
+        Commands::Restart { ids } => restart_items(&*backend, ids)?,
+        Commands::Logs {
+            id,
+            follow,
+            lines,
+            since,
+        } => show_logs(
+            &*backend,
+            &id,
+            &LogOptions {
+                follow,
+                lines,
+                since,
+            },
+        )?,
+        Commands::Apply { manifest, prune } => {
+            let manifest = Manifest::from_toml(
+                &fs::read_to_string(&manifest).map_err(Error::file(&manifest))?,
+            )?;
+            let changes = manifest::plan(&*backend, &manifest, prune)?;
+            if changes.is_empty() {
+                println!("No changes.");
+            }
+            for change in &changes {
+                println!("{change}");
+            }
+            manifest::apply(&*backend, &changes)?;
+        }
+        Commands::Export => {
+            print!(
+                "{}",
+                Manifest::from_items(&get_items_list(&*backend)?).to_toml()?
+            );
+        }
+        Commands::Import {
+            manifest,
+            on_conflict,
+        } => {
+            let manifest = Manifest::from_toml(
+                &fs::read_to_string(&manifest).map_err(Error::file(&manifest))?,
+            )?;
+            let on_conflict = match on_conflict {
+                OnConflictArg::Error => OnConflict::Error,
+                OnConflictArg::Skip => OnConflict::Skip,
+                OnConflictArg::Rename => OnConflict::Rename,
+            };
+            manifest::import(&*backend, &manifest, on_conflict)?;
+        }
+        Commands::Open => open_config_folder(&*backend)?,
         #[cfg(target_os = "windows")]
         Commands::Run {
             command,
             stdout,
             stderr,
-        } => run_no_window(command, stdout, stderr).expect("Failed to run command"),
+            output_mode,
+            cwd,
+            restart,
+            restart_delay,
+            max_restarts,
+            remove_on_success,
+            supervise,
+        } => {
+            // The script sets the environment variables of the item already.
+            let options = RunOptions {
+                stdout,
+                stderr,
+                output_mode: output_mode.into(),
+                env: Default::default(),
+                cwd,
+                restart: restart.into(),
+                restart_delay: std::time::Duration::from_secs(restart_delay),
+                max_restarts,
+                remove_on_success,
+            };
+            if supervise {
+                utils::supervise(command, &options)?;
+            } else {
+                utils::run_no_window(command, &options)?;
+            }
+        }
     }
+    Ok(())
 }
 
-/// List all startup commands with a table.
-pub fn list_items() {
+/// Parse a `KEY=VALUE` argument of `--env`.
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{s}`"))
+}
+
+/// Query the status of every item. An item whose status cannot be queried gets
+/// `None`.
+fn query_statuses(backend: &dyn StartupBackend, items: &[StartupItem]) -> Vec<Option<Status>> {
+    items
+        .iter()
+        .map(|item| {
+            item.status(backend)
+                .inspect_err(|e| warn!("Cannot query the status of `{}`: {e}", item.id))
+                .ok()
+        })
+        .collect()
+}
+
+fn or_empty(value: Option<impl ToString>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
+
+fn or_dash(value: Option<impl ToString>) -> String {
+    value.map_or("-".to_string(), |value| value.to_string())
+}
+
+/// Escape a field of `--format tsv`.
+fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', r"\\")
+        .replace('\t', r"\t")
+        .replace('\n', r"\n")
+        .replace('\r', r"\r")
+}
+
+/// Print a value with `--format json|yaml`.
+fn print_serialized(value: &impl Serialize, format: Format) -> Result<()> {
+    match format {
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(value).map_err(io::Error::other)?
+        ),
+        _ => print!(
+            "{}",
+            serde_norway::to_string(value).map_err(io::Error::other)?
+        ),
+    }
+    Ok(())
+}
+
+/// Print items and their statuses in a machine-readable format.
+fn print_entries(
+    items: &[StartupItem],
+    statuses: Option<&[Option<Status>]>,
+    format: Format,
+) -> Result<()> {
+    let status = |i: usize| statuses.and_then(|statuses| statuses[i].as_ref());
+    let entries = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| Entry {
+            item,
+            status: status(i),
+        })
+        .collect::<Vec<_>>();
+    match format {
+        Format::Json | Format::Yaml => print_serialized(&entries, format)?,
+        Format::Tsv | Format::Table => {
+            let mut header = vec![
+                "id", "name", "command", "path", "enabled", "stdout", "stderr", "restart",
+                "managed",
+            ];
+            if statuses.is_some() {
+                header.extend(["state", "pid", "started", "last_exit_code", "restarts"]);
+            }
+            println!("{}", header.join("\t"));
+            for entry in entries {
+                let item = entry.item;
+                let mut row = vec![
+                    item.id.clone(),
+                    item.name.clone(),
+                    item.command.clone(),
+                    item.path.display().to_string(),
+                    item.enabled.to_string(),
+                    or_empty(item.stdout.as_ref()),
+                    or_empty(item.stderr.as_ref()),
+                    item.restart.to_string(),
+                    item.is_managed().to_string(),
+                ];
+                if statuses.is_some() {
+                    let status = entry.status;
+                    row.extend([
+                        or_empty(status.map(|status| status.state)),
+                        or_empty(status.and_then(|status| status.pid)),
+                        or_empty(status.and_then(|status| status.started.as_ref())),
+                        or_empty(status.and_then(|status| status.last_exit_code)),
+                        or_empty(status.and_then(|status| status.restarts)),
+                    ]);
+                }
+                let row = row.iter().map(|field| tsv_field(field)).collect::<Vec<_>>();
+                println!("{}", row.join("\t"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_item(item: &StartupItem) {
+    println!("id: {}", item.id);
+    println!("path: {}", item.path.display());
+    println!("name: {}", item.name);
+    println!("command: {}", item.command);
+    println!("stdout: {}", or_dash(item.stdout.as_ref()));
+    println!("stderr: {}", or_dash(item.stderr.as_ref()));
+    println!("output mode: {}", item.output_mode);
+    let env = item
+        .env
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+    println!(
+        "env: {}",
+        if env.is_empty() {
+            "-".to_string()
+        } else {
+            env.join(" ")
+        }
+    );
+    println!("env file: {}", or_dash(item.env_file.as_ref()));
+    println!("cwd: {}", item.cwd.as_deref().unwrap_or("~"));
+    println!("restart: {}", item.restart);
+    println!(
+        "restart delay: {}s",
+        item.restart_delay.unwrap_or(DEFAULT_RESTART_DELAY)
+    );
+    println!("max restarts: {}", or_dash(item.max_restarts));
+    println!(
+        "oneshot: {}",
+        match (item.oneshot, item.once) {
+            (true, true) => "yes (removed after the first successful run)",
+            (true, false) => "yes",
+            (false, _) => "no",
+        }
+    );
+    println!("enabled: {}", if item.enabled { "yes" } else { "no" });
+    println!(
+        "managed: {}",
+        item.marker.map_or("no".to_string(), |marker| format!(
+            "yes (format {}, created {})",
+            marker.version, marker.created
+        ))
+    );
+}
+
+fn print_status(item: &StartupItem, status: &Status) {
+    println!("{}: {}", item.id, status.state);
+    println!("  command: {}", item.command);
+    println!("  pid: {}", or_dash(status.pid));
+    println!("  started: {}", or_dash(status.started.as_ref()));
+    println!("  last exit code: {}", or_dash(status.last_exit_code));
+    println!("  restarts: {}", or_dash(status.restarts));
+}
+
+/// List all startup commands with a table. `statuses` are the statuses of the
+/// items, shown as extra columns.
+pub fn list_items(items: &[StartupItem], show_managed: bool, statuses: Option<&[Option<Status>]>) {
     use comfy_table::{
         Table,
         TableComponent::{BottomLeftCorner, BottomRightCorner, TopLeftCorner, TopRightCorner},
@@ -126,11 +803,42 @@ pub fn list_items() {
     table.set_style(TopRightCorner, '╮');
     table.set_style(BottomLeftCorner, '╰');
     table.set_style(BottomRightCorner, '╯');
-    table.set_header(vec!["id", "command"]);
+    let mut header = vec!["id", "command", "enabled", "path"];
+    if show_managed {
+        header.push("managed");
+    }
+    if statuses.is_some() {
+        header.extend(["state", "pid", "started", "last exit code", "restarts"]);
+    }
+    table.set_header(header);
 
-    let items = get_items_list();
-    for (id, command) in items {
-        table.add_row(vec![id, command]);
+    for (i, item) in items.iter().enumerate() {
+        let mut row = vec![
+            item.id.clone(),
+            item.command.clone(),
+            if item.enabled { "yes" } else { "no" }.to_string(),
+            item.path.display().to_string(),
+        ];
+        if show_managed {
+            row.push(if item.is_managed() { "yes" } else { "no" }.to_string());
+        }
+        if let Some(statuses) = statuses {
+            match &statuses[i] {
+                Some(status) => row.extend([
+                    status.state.to_string(),
+                    or_dash(status.pid),
+                    or_dash(status.started.as_ref()),
+                    or_dash(status.last_exit_code),
+                    or_dash(status.restarts),
+                ]),
+                None => row.extend(
+                    ["unknown".to_string()]
+                        .into_iter()
+                        .chain(std::iter::repeat_n("-".to_string(), 4)),
+                ),
+            }
+        }
+        table.add_row(row);
     }
     println!("{table}");
 }
@@ -0,0 +1,312 @@
+//! A declarative list of startup items, kept in a TOML file, e.g. in
+//! dotfiles.
+//!
+//! ```toml
+//! [[item]]
+//! name = "syncthing"
+//! command = "syncthing --no-browser"
+//! stdout = "/tmp/syncthing.log"
+//! restart = "always"
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, OutputMode, Restart, Result, StartupItem, add_item, backend::StartupBackend, edit_item,
+    get_items_list, remove_items, resolve_paths, validate,
+};
+
+/// A list of startup items. Every item is identified by its name, which is
+/// also the id of its startup file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default, rename = "item")]
+    pub items: Vec<ManifestItem>,
+}
+
+/// A startup item in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestItem {
+    pub name: String,
+    pub command: String,
+    /// Environment variables of the command.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// A file of `KEY=VALUE` lines with more environment variables.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+    /// The working directory of the command. Defaults to the home directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+    #[serde(default)]
+    pub output_mode: OutputMode,
+    #[serde(default)]
+    pub restart: Restart,
+    /// How long to wait before restarting the command, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart_delay: Option<u64>,
+    /// How many times the command is restarted at most.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_restarts: Option<u32>,
+    /// The command runs once and exits.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub oneshot: bool,
+    /// Remove the item after its first successful run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub once: bool,
+}
+
+impl ManifestItem {
+    /// The startup item described by this entry.
+    pub fn to_item(&self) -> StartupItem {
+        let mut item = StartupItem::new(&self.command)
+            .name(&self.name)
+            .output_mode(self.output_mode)
+            .restart(self.restart);
+        item.stdout = self.stdout.clone();
+        item.stderr = self.stderr.clone();
+        item.env = self.env.clone();
+        item.env_file = self.env_file.clone();
+        item.cwd = self.cwd.clone();
+        item.restart_delay = self.restart_delay;
+        item.max_restarts = self.max_restarts;
+        // Like `add --once`, which implies `--oneshot`, both never restart.
+        if self.once {
+            item = item.once();
+        } else if self.oneshot {
+            item = item.oneshot();
+        }
+        item
+    }
+}
+
+impl From<&StartupItem> for ManifestItem {
+    /// The entry of an installed item, named by its id.
+    fn from(item: &StartupItem) -> Self {
+        Self {
+            name: item.id.clone(),
+            command: item.command.clone(),
+            env: item.env.clone(),
+            env_file: item.env_file.clone(),
+            cwd: item.cwd.clone(),
+            stdout: item.stdout.clone(),
+            stderr: item.stderr.clone(),
+            output_mode: item.output_mode,
+            restart: item.restart,
+            restart_delay: item.restart_delay,
+            max_restarts: item.max_restarts,
+            oneshot: item.oneshot,
+            once: item.once,
+        }
+    }
+}
+
+impl Manifest {
+    /// A manifest of installed items, e.g. the ones of [`get_items_list`].
+    pub fn from_items(items: &[StartupItem]) -> Self {
+        Self {
+            items: items.iter().map(ManifestItem::from).collect(),
+        }
+    }
+
+    /// Parse a manifest, checking that the items can be added and that their
+    /// names are unique.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use user_startup::{Restart, manifest::Manifest};
+    /// let manifest = Manifest::from_toml(
+    ///     r#"
+    /// [[item]]
+    /// name = "syncthing"
+    /// command = "syncthing --no-browser"
+    /// restart = "always"
+    /// "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(manifest.items[0].restart, Restart::Always);
+    /// assert!(Manifest::from_toml("[[item]]\nname = 'no command'").is_err());
+    /// assert!(Manifest::from_toml("[[item]]\nname = 'a\tb'\ncommand = 'x'").is_err());
+    /// ```
+    pub fn from_toml(s: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(s).map_err(|e| Error::Manifest(e.to_string()))?;
+        for (i, item) in manifest.items.iter().enumerate() {
+            validate(&item.to_item())
+                .map_err(|e| Error::Manifest(format!("item `{}`: {e}", item.name)))?;
+            if manifest.items[..i]
+                .iter()
+                .any(|other| other.name == item.name)
+            {
+                return Err(Error::Manifest(format!("duplicate name `{}`", item.name)));
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| Error::Manifest(e.to_string()))
+    }
+}
+
+/// What [`import`] does with an item whose name is taken by an installed one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnConflict {
+    /// Import nothing, and return [`Error::AlreadyExists`].
+    #[default]
+    Error,
+    /// Skip the item.
+    Skip,
+    /// Add the item with the next free id, e.g. `syncthing1`.
+    Rename,
+}
+
+/// Add the items of a manifest, e.g. one exported on another machine, and
+/// return the added items.
+pub fn import(
+    backend: &dyn StartupBackend,
+    manifest: &Manifest,
+    on_conflict: OnConflict,
+) -> Result<Vec<StartupItem>> {
+    let exists = |item: &ManifestItem| backend.locate(&item.name).is_some();
+    if on_conflict == OnConflict::Error
+        && let Some(item) = manifest.items.iter().find(|item| exists(item))
+    {
+        return Err(Error::AlreadyExists(item.name.clone()));
+    }
+    manifest
+        .items
+        .iter()
+        .filter(|item| on_conflict != OnConflict::Skip || !exists(item))
+        .map(|item| add_item(backend, item.to_item()))
+        .collect()
+}
+
+/// A change made by [`apply`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Add an item which is not installed.
+    Add(ManifestItem),
+    /// Re-render an installed item whose fields changed. The paths of `new`
+    /// are absolute.
+    Update { old: StartupItem, new: ManifestItem },
+    /// Remove an installed item which is not in the manifest.
+    Remove(StartupItem),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Add(item) => write!(f, "+ {}: {}", item.name, item.command),
+            Self::Update { old, new } => {
+                write!(f, "~ {}: ", new.name)?;
+                if old.command == new.command {
+                    write!(f, "{}", new.command)?;
+                } else {
+                    write!(f, "{} -> {}", old.command, new.command)?;
+                }
+                for field in changed_fields(&ManifestItem::from(old), new) {
+                    write!(f, "\n    {field}")?;
+                }
+                Ok(())
+            }
+            Self::Remove(item) => write!(f, "- {}: {}", item.id, item.command),
+        }
+    }
+}
+
+/// The fields other than the name and the command which differ between two
+/// items, as `key: old -> new` with TOML values. Unset fields are `-`.
+fn changed_fields(old: &ManifestItem, new: &ManifestItem) -> Vec<String> {
+    let table = |item: &ManifestItem| match toml::Value::try_from(item) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => toml::Table::new(),
+    };
+    let (old, new) = (table(old), table(new));
+    let value = |value: Option<&toml::Value>| value.map_or("-".to_string(), ToString::to_string);
+    old.keys()
+        .chain(new.keys())
+        .filter(|key| !["name", "command"].contains(&key.as_str()))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| format!("{key}: {} -> {}", value(old.get(key)), value(new.get(key))))
+        .collect()
+}
+
+/// Compare the manifest with the installed items, and return the changes
+/// [`apply`] would make. Managed items not in the manifest are only removed
+/// with `prune`.
+///
+/// Returns [`Error::NotManaged`] if the id of an item is taken by a startup
+/// file which was not created by user-startup.
+pub fn plan(backend: &dyn StartupBackend, manifest: &Manifest, prune: bool) -> Result<Vec<Change>> {
+    let installed = get_items_list(backend)?;
+    let mut changes = vec![];
+    for wanted in &manifest.items {
+        match installed.iter().find(|item| item.id == wanted.name) {
+            Some(item) => {
+                // Fields a backend does not keep, e.g. the max restarts of
+                // launchd, cannot be compared, so the rendered files are.
+                let mut new = wanted.to_item();
+                resolve_paths(&mut new)?;
+                (new.id, new.path, new.marker, new.enabled) = (
+                    item.id.clone(),
+                    item.path.clone(),
+                    item.marker,
+                    item.enabled,
+                );
+                if backend.render(&new)?
+                    != fs::read_to_string(&item.path).map_err(Error::file(&item.path))?
+                {
+                    changes.push(Change::Update {
+                        old: item.clone(),
+                        new: ManifestItem::from(&new),
+                    });
+                }
+            }
+            None if backend.locate(&wanted.name).is_some() => {
+                return Err(Error::NotManaged(wanted.name.clone()));
+            }
+            None => changes.push(Change::Add(wanted.clone())),
+        }
+    }
+    if prune {
+        changes.extend(
+            installed
+                .into_iter()
+                .filter(|item| !manifest.items.iter().any(|wanted| wanted.name == item.id))
+                .map(Change::Remove),
+        );
+    }
+    Ok(changes)
+}
+
+/// Make the changes returned by [`plan`]. Stops at the first change which
+/// fails.
+pub fn apply(backend: &dyn StartupBackend, changes: &[Change]) -> Result<()> {
+    for change in changes {
+        match change {
+            Change::Add(wanted) => {
+                add_item(backend, wanted.to_item())?;
+            }
+            Change::Update { old, new } => {
+                // The id, the path, the marker and whether it is enabled are
+                // kept by `edit_item`.
+                edit_item(backend, &old.id, |item| *item = new.to_item())?;
+            }
+            Change::Remove(item) => remove_items(backend, vec![item.id.clone()], false)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,309 @@
+//! Run the commands of the service managers, e.g. `launchctl`, and change
+//! startup files.
+
+use std::{
+    fmt, fs,
+    io::Write,
+    path::Path,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use log::debug;
+
+use crate::{Error, Result};
+
+/// The output of a finished command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// The exit code, `None` if the process was killed by a signal.
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    /// A successful output with `stdout`.
+    pub fn ok(stdout: impl Into<String>) -> Self {
+        Self {
+            code: Some(0),
+            stdout: stdout.into(),
+            stderr: String::new(),
+        }
+    }
+
+    /// A failed output with exit code `code` and `stderr`.
+    pub fn failed(code: i32, stderr: impl Into<String>) -> Self {
+        Self {
+            code: Some(code),
+            stdout: String::new(),
+            stderr: stderr.into(),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Turn a non-zero exit code into [`Error::CommandFailed`].
+fn check(program: &str, args: &[&str], output: CommandOutput) -> Result<CommandOutput> {
+    if !output.success() {
+        return Err(Error::CommandFailed {
+            command: display_command(program, args),
+            code: output.code,
+            stderr: output.stderr,
+        });
+    }
+    Ok(output)
+}
+
+/// Runs a command and waits for it to finish. Backends run every external
+/// command and change every file through a runner, so it can be replaced in
+/// tests or for a dry run.
+pub trait CommandRunner: Send + Sync {
+    /// Run a command which changes the state of the machine.
+    fn run(&self, program: &str, args: &[&str]) -> Result<CommandOutput>;
+
+    /// Run a command, and turn a non-zero exit code into
+    /// [`Error::CommandFailed`].
+    fn run_checked(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        check(program, args, self.run(program, args)?)
+    }
+
+    /// Run a command which only reads the state of the machine, e.g.
+    /// `id -u`, so it is run even in a dry run.
+    fn query(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        self.run(program, args)
+    }
+
+    /// [`CommandRunner::query`], turning a non-zero exit code into
+    /// [`Error::CommandFailed`].
+    fn query_checked(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        check(program, args, self.query(program, args)?)
+    }
+
+    /// Run a command with the stdio of this process, e.g. to follow logs, and
+    /// turn a non-zero exit code into [`Error::CommandFailed`].
+    fn run_interactive(&self, program: &str, args: &[&str]) -> Result<()> {
+        let output = self.query_checked(program, args)?;
+        print!("{}", output.stdout);
+        Ok(())
+    }
+
+    fn write_file(&self, path: &Path, content: &str) -> Result<()> {
+        fs::write(path, content).map_err(Error::file(path))?;
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        fs::remove_file(path).map_err(Error::file(path))?;
+        Ok(())
+    }
+
+    /// Create a directory and its parents if they do not exist.
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path).map_err(Error::file(path))?;
+        Ok(())
+    }
+
+    /// Create a symbolic link at `link` pointing to `original`, creating the
+    /// directory of the link if needed.
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        if let Some(dir) = link.parent() {
+            fs::create_dir_all(dir).map_err(Error::file(dir))?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(original, link).map_err(Error::file(link))?;
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_file(original, link).map_err(Error::file(link))?;
+        Ok(())
+    }
+
+    /// Move a file, creating the directory it is moved to if needed.
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        if let Some(dir) = to.parent() {
+            fs::create_dir_all(dir).map_err(Error::file(dir))?;
+        }
+        fs::rename(from, to).map_err(Error::file(from))?;
+        Ok(())
+    }
+}
+
+/// Runs commands as child processes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessRunner;
+
+impl CommandRunner for ProcessRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        debug!("Executing `{}`", display_command(program, args));
+        let output = Command::new(program).args(args).output()?;
+        Ok(CommandOutput {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    fn run_interactive(&self, program: &str, args: &[&str]) -> Result<()> {
+        debug!("Executing `{}`", display_command(program, args));
+        let status = Command::new(program).args(args).status()?;
+        if !status.success() {
+            return Err(Error::CommandFailed {
+                command: display_command(program, args),
+                code: status.code(),
+                // It was printed already.
+                stderr: String::new(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Records the commands instead of running them, for tests. Every command
+/// succeeds with an empty output, unless told otherwise by
+/// [`RecordingRunner::respond`].
+///
+/// # Examples
+///
+/// ```rust
+/// use user_startup::runner::{CommandOutput, CommandRunner, RecordingRunner};
+/// let runner = RecordingRunner::new().respond("id -u", CommandOutput::ok("501\n"));
+/// assert_eq!(runner.run("id", &["-u"]).unwrap().stdout, "501\n");
+/// assert!(runner.run("true", &[]).unwrap().success());
+/// assert_eq!(runner.calls(), ["id -u", "true"]);
+/// ```
+#[derive(Debug, Default)]
+pub struct RecordingRunner {
+    calls: Mutex<Vec<String>>,
+    responses: Vec<(String, CommandOutput)>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the commands starting with `prefix` with `output`. The first
+    /// matching prefix wins.
+    pub fn respond(mut self, prefix: impl Into<String>, output: CommandOutput) -> Self {
+        self.responses.push((prefix.into(), output));
+        self
+    }
+
+    /// The commands run so far, each joined with spaces.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        let command = display_command(program, args);
+        let output = self
+            .responses
+            .iter()
+            .find(|(prefix, _)| command.starts_with(prefix.as_str()))
+            .map_or_else(|| CommandOutput::ok(""), |(_, output)| output.clone());
+        self.calls.lock().unwrap().push(command);
+        Ok(output)
+    }
+}
+
+/// Prints the commands and file changes to `output` instead of making them.
+/// Queries and interactive commands are passed to the inner runner.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::{io, path::Path, sync::Arc};
+///
+/// use user_startup::runner::{CommandRunner, DryRunRunner, RecordingRunner};
+/// let inner = Arc::new(RecordingRunner::new());
+/// let runner = DryRunRunner::new(inner.clone(), io::stdout());
+/// runner.run("systemctl", &["--user", "enable", "foo.service"]).unwrap();
+/// runner.query("id", &["-u"]).unwrap();
+/// runner.write_file(Path::new("/nonexistent/foo.service"), "[Unit]").unwrap();
+/// assert_eq!(inner.calls(), ["id -u"]);
+/// ```
+pub struct DryRunRunner {
+    inner: Arc<dyn CommandRunner>,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl DryRunRunner {
+    pub fn new(inner: Arc<dyn CommandRunner>, output: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            output: Mutex::new(Box::new(output)),
+        }
+    }
+
+    /// Print a line of what would be done.
+    fn print(&self, line: fmt::Arguments) -> Result<()> {
+        writeln!(self.output.lock().unwrap(), "{line}")?;
+        Ok(())
+    }
+}
+
+impl CommandRunner for DryRunRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        self.print(format_args!(
+            "Would run `{}`",
+            display_command(program, args)
+        ))?;
+        Ok(CommandOutput::ok(""))
+    }
+
+    fn query(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        self.inner.query(program, args)
+    }
+
+    fn run_interactive(&self, program: &str, args: &[&str]) -> Result<()> {
+        self.inner.run_interactive(program, args)
+    }
+
+    fn write_file(&self, path: &Path, content: &str) -> Result<()> {
+        let mut output = self.output.lock().unwrap();
+        writeln!(output, "Would write `{}`:", path.display())?;
+        write!(output, "{content}")?;
+        if !content.ends_with('\n') {
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.print(format_args!("Would remove `{}`", path.display()))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        if !path.is_dir() {
+            self.print(format_args!("Would create `{}`", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        self.print(format_args!(
+            "Would link `{}` to `{}`",
+            link.display(),
+            original.display()
+        ))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.print(format_args!(
+            "Would move `{}` to `{}`",
+            from.display(),
+            to.display()
+        ))
+    }
+}
+
+fn display_command(program: &str, args: &[&str]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
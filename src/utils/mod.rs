@@ -1,17 +1,19 @@
-#[cfg(target_os = "linux")]
-mod linux;
-#[cfg(target_os = "macos")]
-mod macos;
 #[cfg(target_os = "windows")]
 mod windows;
 
-#[cfg(target_os = "linux")]
-pub use linux::*;
-#[cfg(target_os = "macos")]
-pub use macos::*;
 #[cfg(target_os = "windows")]
 pub use windows::*;
 
+/// The command used to open a folder in the file manager.
+#[cfg(target_os = "linux")]
+pub const OPEN_COMMAND: &str = "xdg-open";
+#[cfg(target_os = "macos")]
+pub const OPEN_COMMAND: &str = "open";
+
+/// The editor used when neither `$VISUAL` nor `$EDITOR` is set.
+#[cfg(not(target_os = "windows"))]
+pub const DEFAULT_EDITOR: &str = "vi";
+
 pub trait IteratorExt: Iterator {
     /// Split the iterator into two parts when the predicate first time turns
     /// true.
@@ -49,9 +51,11 @@ impl<I: Iterator> IteratorExt for I {
 ///
 /// Note that the returned executable string will not contains leading and
 /// suffix quotes.
-pub fn parse_command(command: impl AsRef<str>) -> (String, String) {
+///
+/// Returns `None` if the command is blank or the executable is empty.
+pub fn parse_command(command: impl AsRef<str>) -> Option<(String, String)> {
     let mut command = command.as_ref().trim().chars().peekable();
-    if ['\'', '"'].contains(command.peek().expect("command is empty")) {
+    let (executable, args) = if ['\'', '"'].contains(command.peek()?) {
         let first_quote = command.next().unwrap();
         let (executable, rest) = command.split_when(|c| c == &first_quote);
         let args = rest.skip_while(|c| *c == ' ').collect::<String>();
@@ -59,7 +63,97 @@ pub fn parse_command(command: impl AsRef<str>) -> (String, String) {
     } else {
         let (executable, args) = command.split_when(|c| *c == ' ');
         (executable.collect::<String>(), args.collect::<String>())
+    };
+    (!executable.is_empty()).then_some((executable, args))
+}
+
+/// Split a command into an argv. The executable is taken by
+/// [`parse_command`], and the arguments are split like a POSIX shell does:
+/// single quotes keep everything literally, double quotes keep everything but
+/// backslash escapes of `"`, `\\`, `$` and `` ` ``, and a backslash outside
+/// quotes escapes the next character.
+///
+/// Returns `None` if a quote is not closed, or if [`parse_command`] does.
+///
+/// # Examples
+///
+/// ```rust
+/// use user_startup::utils::split_command;
+/// assert_eq!(
+///     split_command(r#"rclone mount 'my drive:' "$HOME/my drive" --vfs\ cache"#).unwrap(),
+///     ["rclone", "mount", "my drive:", "$HOME/my drive", "--vfs cache"]
+/// );
+/// assert_eq!(split_command("echo 'unclosed"), None);
+/// assert_eq!(split_command("  "), None);
+/// ```
+pub fn split_command(command: impl AsRef<str>) -> Option<Vec<String>> {
+    let (executable, rest) = parse_command(command)?;
+    let mut argv = vec![executable];
+    let mut chars = rest.chars();
+    // `None` between arguments, `Some` while inside one.
+    let mut current: Option<String> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => argv.extend(current.take()),
+            '\'' => {
+                let arg = current.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '\\' | '$' | '`') => arg.push(c),
+                            c => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                        },
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = current.get_or_insert_default();
+                arg.extend(chars.next());
+            }
+            c => current.get_or_insert_default().push(c),
+        }
     }
+    argv.extend(current);
+    Some(argv)
+}
+
+/// Join an argv into a command, the reverse of [`split_command`]. Arguments
+/// which would be split or unquoted are wrapped in single quotes.
+///
+/// # Examples
+///
+/// ```rust
+/// use user_startup::utils::join_command;
+/// let argv = ["rclone", "mount", "my drive:", "it's"].map(String::from);
+/// assert_eq!(join_command(&argv), r#"rclone mount 'my drive:' 'it'\''s'"#);
+/// ```
+pub fn join_command(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && !arg.contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '\\'))
+            {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 #[cfg(test)]
@@ -78,20 +172,54 @@ mod tests {
     fn test_parse_command() {
         // Test with no quotes around the executable
         let command = r#"ppp arg1 "'arg 2 with spaces'""#;
-        let (executable, args) = parse_command(command);
+        let (executable, args) = parse_command(command).unwrap();
         assert_eq!(executable, "ppp");
         assert_eq!(args, "arg1 \"'arg 2 with spaces'\"");
 
         // Test with single quotes around the executable
         let command = r#"'C:\Program Files\My App\myapp.exe' arg1 'arg 2 with spaces'"#;
-        let (executable, args) = parse_command(command);
+        let (executable, args) = parse_command(command).unwrap();
         assert_eq!(executable, "C:\\Program Files\\My App\\myapp.exe");
         assert_eq!(args, "arg1 'arg 2 with spaces'");
 
         // Test with double quotes around the executable
         let command = r#""C:\Program Files\My App\myapp.exe" arg1 'arg 2 with spaces'"#;
-        let (executable, args) = parse_command(command);
+        let (executable, args) = parse_command(command).unwrap();
         assert_eq!(executable, "C:\\Program Files\\My App\\myapp.exe");
         assert_eq!(args, "arg1 'arg 2 with spaces'");
+
+        assert_eq!(parse_command(""), None);
+        assert_eq!(parse_command(" \t "), None);
+        assert_eq!(parse_command("'' arg"), None);
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(split_command("ppp").unwrap(), ["ppp"]);
+        assert_eq!(
+            split_command(r#"ppp arg1 "'arg 2 with spaces'" ''"#).unwrap(),
+            ["ppp", "arg1", "'arg 2 with spaces'", ""]
+        );
+        assert_eq!(
+            split_command(r#""C:\Program Files\My App\myapp.exe" -a"b c"d"#).unwrap(),
+            ["C:\\Program Files\\My App\\myapp.exe", "-ab cd"]
+        );
+        assert_eq!(
+            split_command(r#"sh -c "echo \"\$HOME\" \n" a\;"#).unwrap(),
+            ["sh", "-c", "echo \"$HOME\" \\n", "a;"]
+        );
+        assert_eq!(split_command(r#"sh -c "unclosed"#), None);
+    }
+
+    #[test]
+    fn test_join_command_roundtrip() {
+        for command in [
+            "ppp",
+            r#"ppp arg1 "'arg 2 with spaces'" ''"#,
+            r#"sh -c 'echo "$HOME" \\n' a\;"#,
+        ] {
+            let argv = split_command(command).unwrap();
+            assert_eq!(split_command(join_command(&argv)).unwrap(), argv);
+        }
     }
 }
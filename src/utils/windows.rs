@@ -1,73 +1,166 @@
 use std::{
-    fs::File,
+    collections::BTreeMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io,
     os::windows::process::CommandExt,
     path::{Path, PathBuf},
-    process::Command,
-    sync::LazyLock as Lazy,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
 };
 
 use super::parse_command;
+use crate::{DEFAULT_RESTART_DELAY, OutputMode, Restart};
 
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-pub static CONFIG_PATH: Lazy<PathBuf> = Lazy::new(|| {
-    dirs::home_dir()
-        .expect("Could not find home directory")
-        .join("AppData")
-        .join("Roaming")
-        .join("Microsoft")
-        .join("Windows")
-        .join("Start Menu")
-        .join("Programs")
-        .join("Startup")
-});
-
-pub const COMMENT_PREFIX: &str = ":: ";
-
-pub fn comment(s: &str) -> String {
-    format!("{COMMENT_PREFIX}{s}")
-}
-
 pub const OPEN_COMMAND: &str = "explorer";
 
-pub const FILE_EXT: &str = ".cmd";
+/// The editor used when neither `$VISUAL` nor `$EDITOR` is set.
+pub const DEFAULT_EDITOR: &str = "notepad";
 
-fn escape_quotes(s: impl AsRef<str>) -> String {
-    s.as_ref().replace("\"", "^\"")
+/// How [`run_no_window`] runs a command.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Redirect stdout to this file.
+    pub stdout: Option<PathBuf>,
+    /// Redirect stderr to this file.
+    pub stderr: Option<PathBuf>,
+    /// How the files of `stdout` and `stderr` are opened on every start.
+    pub output_mode: OutputMode,
+    /// Added to the environment of the command.
+    pub env: BTreeMap<String, String>,
+    /// The working directory. The current directory if `None`.
+    pub cwd: Option<PathBuf>,
+    pub restart: Restart,
+    pub restart_delay: Duration,
+    /// How many times the command is restarted at most. Unlimited if `None`.
+    pub max_restarts: Option<u32>,
+    /// Remove this startup script once the command succeeded.
+    pub remove_on_success: Option<PathBuf>,
 }
 
-pub fn format(cmd: &str, _: Option<&str>, stdout: Option<&str>, stderr: Option<&str>) -> String {
-    format!(
-        r#"{prefixed_cmd}
-"{self_bin}" run "{cmd}" {stdout} {stderr}
-"#,
-        self_bin = std::env::current_exe()
-            .expect("Failed to get current executable path")
-            .display(),
-        prefixed_cmd = comment(cmd),
-        cmd = escape_quotes(cmd),
-        stdout = stdout.map_or(String::new(), |s| format!("--stdout {s}")),
-        stderr = stderr.map_or(String::new(), |s| format!("--stderr {s}"))
-    )
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            stdout: None,
+            stderr: None,
+            output_mode: OutputMode::Append,
+            env: BTreeMap::new(),
+            cwd: None,
+            restart: Restart::Never,
+            restart_delay: Duration::from_secs(DEFAULT_RESTART_DELAY),
+            max_restarts: None,
+            remove_on_success: None,
+        }
+    }
 }
 
-/// Run a command with NO_WINDOW.
-pub fn run_no_window(
-    cmd: impl AsRef<str>,
-    stdout: Option<impl AsRef<Path>>,
-    stderr: Option<impl AsRef<Path>>,
-) -> std::io::Result<()> {
-    let (bin, rest) = parse_command(cmd);
+/// The command without a window, with its output files opened as
+/// [`RunOptions::output_mode`] says.
+fn command_no_window(cmd: &str, options: &RunOptions) -> io::Result<Command> {
+    let open = |path: &Path| match options.output_mode {
+        OutputMode::Append => OpenOptions::new().create(true).append(true).open(path),
+        OutputMode::File => OpenOptions::new().create(true).write(true).open(path),
+        OutputMode::Truncate => File::create(path),
+    };
+    let (bin, rest) = parse_command(cmd).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid command `{cmd}`"),
+        )
+    })?;
     let mut command = Command::new(bin);
-    command.creation_flags(CREATE_NO_WINDOW).raw_arg(rest);
-    if let Some(stdout) = stdout {
-        let f = File::create(stdout)?;
-        command.stdout(f);
+    // Without a window there is nothing to write to. Inherited pipes would
+    // also keep whoever runs the script waiting for the command.
+    command
+        .creation_flags(CREATE_NO_WINDOW)
+        .raw_arg(rest)
+        .envs(&options.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(cwd) = &options.cwd {
+        command.current_dir(cwd);
+    }
+    if let Some(stdout) = &options.stdout {
+        command.stdout(open(stdout)?);
+    }
+    if let Some(stderr) = &options.stderr {
+        command.stderr(open(stderr)?);
     }
-    if let Some(stderr) = stderr {
-        let f = File::create(stderr)?;
-        command.stderr(f);
+    Ok(command)
+}
+
+/// Run a command with NO_WINDOW. A command which is restarted, or whose
+/// script is removed, is run by a `user-startup run --supervise` process
+/// without a window, see [`supervise`].
+pub fn run_no_window(cmd: impl AsRef<str>, options: &RunOptions) -> io::Result<()> {
+    let cmd = cmd.as_ref();
+    if options.restart == Restart::Never && options.remove_on_success.is_none() {
+        command_no_window(cmd, options)?.spawn()?;
+        return Ok(());
+    }
+    let mut supervisor = Command::new(env::current_exe()?);
+    supervisor
+        .creation_flags(CREATE_NO_WINDOW)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .args([
+            "run",
+            cmd,
+            "--supervise",
+            "--restart",
+            options.restart.as_str(),
+            "--output-mode",
+            options.output_mode.as_str(),
+        ])
+        .arg("--restart-delay")
+        .arg(options.restart_delay.as_secs().to_string())
+        .envs(&options.env);
+    for (flag, path) in [
+        ("--stdout", &options.stdout),
+        ("--stderr", &options.stderr),
+        ("--cwd", &options.cwd),
+        ("--remove-on-success", &options.remove_on_success),
+    ] {
+        if let Some(path) = path {
+            supervisor.arg(flag).arg(path);
+        }
+    }
+    if let Some(max_restarts) = options.max_restarts {
+        supervisor
+            .arg("--max-restarts")
+            .arg(max_restarts.to_string());
     }
-    command.spawn()?;
+    supervisor.spawn()?;
     Ok(())
 }
+
+/// Run a command with NO_WINDOW, and run it again after the restart delay
+/// when it exits, as the restart policy says. Returns once the command is not
+/// restarted anymore, removing the startup script if it succeeded.
+pub fn supervise(cmd: impl AsRef<str>, options: &RunOptions) -> io::Result<()> {
+    let mut restarts = 0;
+    loop {
+        // Like systemd, every run opens the output files again.
+        let status = command_no_window(cmd.as_ref(), options)?.spawn()?.wait()?;
+        let again = match options.restart {
+            Restart::Never => false,
+            Restart::OnFailure => !status.success(),
+            Restart::Always => true,
+        };
+        if !again || options.max_restarts.is_some_and(|max| restarts >= max) {
+            if let Some(script) = &options.remove_on_success
+                && status.success()
+            {
+                fs::remove_file(script)?;
+            }
+            return Ok(());
+        }
+        restarts += 1;
+        thread::sleep(options.restart_delay);
+    }
+}
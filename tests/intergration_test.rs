@@ -1,7 +1,18 @@
-use std::{collections::HashSet, fs, vec};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    vec,
+};
 
-use log::{LevelFilter, warn};
-use user_startup::{add_item, utils};
+use log::LevelFilter;
+use tempfile::TempDir;
+use user_startup::{
+    StartupItem, add_item, backend,
+    backend::StartupBackend,
+    runner::{CommandOutput, DryRunRunner, RecordingRunner},
+};
 
 /// a - b
 fn vec_diff<T: Eq + std::hash::Hash + Clone>(a: &[T], b: &[T]) -> HashSet<T> {
@@ -15,25 +26,33 @@ fn vec_diff<T: Eq + std::hash::Hash + Clone>(a: &[T], b: &[T]) -> HashSet<T> {
     set
 }
 
-/// log and path init
-fn test_init() {
+/// log init, and a Startup folder backend in a new config directory, which
+/// is removed when dropped
+fn test_init() -> (TempDir, backend::StartupFolder) {
     _ = pretty_env_logger::formatted_builder()
         .filter_level(LevelFilter::Debug)
         .try_init();
-    let config_path = &utils::CONFIG_PATH;
-    if !config_path.exists() {
-        warn!("Config path not found. Creating it...");
-        fs::create_dir_all(config_path.as_os_str()).expect("Failed to create config directory");
-    }
+    let config_dir = tempfile::tempdir().unwrap();
+    let backend = backend::StartupFolder::with_config_dir(config_dir.path());
+    (config_dir, backend)
 }
 
 #[test]
 fn intergration_test() {
-    test_init();
-    let start = user_startup::get_items_list();
-    add_item("myusrtest", None, None, None);
-    add_item("myusrtest", None, None, None);
-    let items = user_startup::get_items_list();
+    let (_config_dir, backend) = test_init();
+    let id_and_command = |item: StartupItem| (item.id, item.command);
+    let start = user_startup::get_items_list(&backend)
+        .unwrap()
+        .into_iter()
+        .map(id_and_command)
+        .collect::<Vec<_>>();
+    add_item(&backend, StartupItem::new("myusrtest")).unwrap();
+    add_item(&backend, StartupItem::new("myusrtest")).unwrap();
+    let items = user_startup::get_items_list(&backend)
+        .unwrap()
+        .into_iter()
+        .map(id_and_command)
+        .collect::<Vec<_>>();
     assert_eq!(items.len(), start.len() + 2);
     assert_eq!(
         vec_diff(&items, &start),
@@ -42,5 +61,503 @@ fn intergration_test() {
             ("myusrtest1".to_string(), "myusrtest".to_string())
         ])
     );
-    user_startup::remove_items(vec!["myusrtest".to_string(), "myusrtest1".to_string()]);
+    user_startup::remove_items(
+        &backend,
+        vec!["myusrtest".to_string(), "myusrtest1".to_string()],
+        false,
+    )
+    .unwrap();
+}
+
+/// A config directory systemd does not search is changed without
+/// `systemctl`, which could not find its units.
+#[test]
+#[cfg(target_os = "linux")]
+fn config_dir_cli_test() {
+    use std::{path::Path, process::Command};
+
+    let config_dir = tempfile::tempdir().unwrap();
+    let run = |editor: &str, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_user-startup"))
+            .arg("--config-dir")
+            .arg(config_dir.path())
+            .args(args)
+            .env("PATH", "")
+            // An empty `$VISUAL` is ignored.
+            .env("VISUAL", "")
+            .env("EDITOR", editor)
+            .output()
+            .unwrap()
+    };
+    let user_startup = |args: &[&str]| {
+        let output = run("/bin/true", args);
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    user_startup(&["add", "sleep 100"]);
+    let link = config_dir
+        .path()
+        .join("default.target.wants")
+        .join("sleep.service");
+    assert_eq!(fs::read_link(&link).unwrap(), Path::new("../sleep.service"));
+    assert!(user_startup(&["list"]).contains("sleep 100"));
+    user_startup(&["edit", "sleep"]);
+    // Deleting the marker line would make the unit unmanaged.
+    let unit = fs::read_to_string(config_dir.path().join("sleep.service")).unwrap();
+    assert!(!run("/bin/sed -i 1d", &["edit", "sleep"]).status.success());
+    assert_eq!(
+        fs::read_to_string(config_dir.path().join("sleep.service")).unwrap(),
+        unit
+    );
+
+    user_startup(&["disable", "sleep"]);
+    assert!(link.symlink_metadata().is_err());
+    user_startup(&["remove", "sleep"]);
+    assert!(!config_dir.path().join("sleep.service").exists());
+}
+
+#[test]
+fn config_dir_test() {
+    let (config_dir, backend) = test_init();
+    let item = add_item(&backend, StartupItem::new("myusrtest --flag")).unwrap();
+    assert_eq!(item.path, config_dir.path().join("myusrtest.cmd"));
+    assert!(item.path.exists());
+
+    let items = user_startup::get_items_list(&backend).unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].command, "myusrtest --flag");
+
+    user_startup::remove_items(&backend, vec!["myusrtest".to_string()], false).unwrap();
+    assert!(user_startup::get_items_list(&backend).unwrap().is_empty());
+
+    // `user-startup run` opens the output files like systemd.
+    let stdout = config_dir.path().join("100%.log");
+    let item = add_item(
+        &backend,
+        StartupItem::new("myusrtest")
+            .stdout(stdout.to_string_lossy())
+            .output_mode(user_startup::OutputMode::Truncate),
+    )
+    .unwrap();
+    let content = fs::read_to_string(&item.path).unwrap();
+    assert!(content.contains(" --output-mode truncate"));
+    let read = user_startup::get_item(&backend, &item.id).unwrap();
+    assert_eq!(read.stdout, item.stdout);
+    assert_eq!(read.output_mode, user_startup::OutputMode::Truncate);
+}
+
+#[test]
+fn ownership_test() {
+    let (config_dir, backend) = test_init();
+    let path = config_dir.path().join("handwritten.cmd");
+    fs::write(&path, ":: my own script\r\nstart notepad\r\n").unwrap();
+
+    assert!(user_startup::get_items_list(&backend).unwrap().is_empty());
+    let all = user_startup::get_all_items(&backend).unwrap();
+    assert_eq!(all.len(), 1);
+    assert!(!all[0].is_managed());
+
+    let ids = vec!["handwritten".to_string()];
+    assert!(matches!(
+        user_startup::remove_items(&backend, ids.clone(), false),
+        Err(user_startup::Error::NotManaged(_))
+    ));
+    assert!(path.exists());
+    user_startup::remove_items(&backend, ids, true).unwrap();
+    assert!(!path.exists());
+}
+
+#[test]
+fn systemd_runner_test() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let runner = Arc::new(RecordingRunner::new().respond(
+        "systemctl --user daemon-reload",
+        CommandOutput::failed(1, "Failed to connect to bus"),
+    ));
+    let backend = backend::Systemd::with_config_dir(config_dir.path())
+        .with_runner(runner.clone())
+        .offline(false);
+    let item = add_item(&backend, StartupItem::new("myusrtest --flag")).unwrap();
+    assert!(item.path.exists());
+    user_startup::edit_item(&backend, "myusrtest", |item| item.command = "other".into()).unwrap();
+    user_startup::remove_items(&backend, vec!["myusrtest".to_string()], false).unwrap();
+    assert!(!item.path.exists());
+    assert_eq!(
+        runner.calls(),
+        [
+            "systemctl --user daemon-reload",
+            "systemctl --user enable myusrtest.service",
+            "systemctl --user daemon-reload",
+            "systemctl --user disable myusrtest.service",
+        ]
+    );
+
+    // A failed `systemctl enable` is an error.
+    let runner = Arc::new(RecordingRunner::new().respond(
+        "systemctl --user enable",
+        CommandOutput::failed(1, "Unit file is masked."),
+    ));
+    let backend = backend::Systemd::with_config_dir(config_dir.path())
+        .with_runner(runner)
+        .offline(false);
+    assert!(matches!(
+        add_item(&backend, StartupItem::new("myusrtest")),
+        Err(user_startup::Error::CommandFailed { code: Some(1), .. })
+    ));
+    assert!(!item.path.exists());
+}
+
+#[test]
+fn enable_disable_test() {
+    let (_config_dir, backend) = test_init();
+    let item = add_item(&backend, StartupItem::new("myusrtest")).unwrap();
+    let ids = vec!["myusrtest".to_string()];
+    let enabled = |backend: &backend::StartupFolder| {
+        user_startup::get_items_list(backend)
+            .unwrap()
+            .iter()
+            .map(|item| item.enabled)
+            .collect::<Vec<_>>()
+    };
+
+    user_startup::disable_items(&backend, ids.clone()).unwrap();
+    assert!(!item.path.exists());
+    assert_eq!(enabled(&backend), [false]);
+    // The id stays taken while disabled.
+    let other = add_item(&backend, StartupItem::new("myusrtest")).unwrap();
+    assert_eq!(other.id, "myusrtest1");
+
+    user_startup::enable_items(&backend, ids.clone()).unwrap();
+    assert!(item.path.exists());
+    assert_eq!(enabled(&backend), [true, true]);
+
+    user_startup::disable_items(&backend, ids.clone()).unwrap();
+    user_startup::remove_items(&backend, ids, false).unwrap();
+    assert_eq!(enabled(&backend), [true]);
+    assert!(matches!(
+        user_startup::enable_items(&backend, vec!["myusrtest".to_string()]),
+        Err(user_startup::Error::NotFound(_))
+    ));
+    // Started scripts are not tracked, so they cannot be stopped.
+    assert!(matches!(
+        user_startup::stop_items(&backend, vec!["myusrtest1".to_string()]),
+        Err(user_startup::Error::Unsupported {
+            operation: "stop",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn edit_test() {
+    let (config_dir, backend) = test_init();
+    let item = add_item(&backend, StartupItem::new("myusrtest --old")).unwrap();
+    user_startup::disable_items(&backend, vec![item.id.clone()]).unwrap();
+
+    let stdout = config_dir.path().join("logs").join("out.log");
+    let edited = user_startup::edit_item(&backend, "myusrtest", |item| {
+        item.command = "othercmd --new".to_string();
+        item.stdout = Some(stdout.to_string_lossy().into_owned());
+    })
+    .unwrap();
+    assert_eq!(edited.id, "myusrtest");
+    assert_eq!(edited.marker, item.marker);
+    assert!(!edited.enabled);
+    assert!(stdout.parent().unwrap().is_dir());
+
+    let read = user_startup::get_item(&backend, "myusrtest").unwrap();
+    assert_eq!(read.command, "othercmd --new");
+    assert_eq!(read.stdout, edited.stdout);
+    assert!(!read.enabled);
+    assert_eq!(user_startup::get_items_list(&backend).unwrap().len(), 1);
+
+    assert!(matches!(
+        user_startup::edit_item(&backend, "myusrtest", |item| item.command.clear()),
+        Err(user_startup::Error::InvalidCommand(_))
+    ));
+    fs::write(
+        config_dir.path().join("handwritten.cmd"),
+        ":: start notepad\r\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        user_startup::edit_item(&backend, "handwritten", |_| {}),
+        Err(user_startup::Error::NotManaged(_))
+    ));
+    assert!(matches!(
+        user_startup::edit_item_file(&backend, "handwritten"),
+        Err(user_startup::Error::NotManaged(_))
+    ));
+}
+
+/// The output of a dry run, shared with the runner writing it.
+#[derive(Clone, Default)]
+struct DryRunOutput(Arc<Mutex<Vec<u8>>>);
+
+impl Write for DryRunOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl DryRunOutput {
+    fn lines(&self) -> Vec<String> {
+        let output = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        output.lines().map(str::to_string).collect()
+    }
+}
+
+#[test]
+fn dry_run_test() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let runner = Arc::new(RecordingRunner::new());
+    let output = DryRunOutput::default();
+    let backend = backend::Systemd::with_config_dir(config_dir.path())
+        .with_runner(Arc::new(DryRunRunner::new(runner.clone(), output.clone())));
+    let stdout = config_dir.path().join("logs").join("out.log");
+    let item = add_item(
+        &backend,
+        StartupItem::new("myusrtest").stdout(stdout.to_string_lossy()),
+    )
+    .unwrap();
+    assert_eq!(item.path, config_dir.path().join("myusrtest.service"));
+    assert!(!item.path.exists());
+    assert!(!stdout.parent().unwrap().exists());
+    // The file which would be written is started by its path.
+    backend.start(&item.path).unwrap();
+    let lines = output.lines();
+    assert_eq!(
+        lines[0],
+        format!("Would create `{}`", stdout.parent().unwrap().display())
+    );
+    assert_eq!(lines[1], format!("Would write `{}`:", item.path.display()));
+    assert_eq!(
+        lines.last().unwrap(),
+        "Would run `systemctl --user start myusrtest.service`"
+    );
+
+    fs::write(&item.path, backend.render(&item).unwrap()).unwrap();
+    user_startup::remove_items(&backend, vec!["myusrtest".to_string()], false).unwrap();
+    assert!(item.path.exists());
+    assert!(runner.calls().is_empty());
+
+    // A config directory which would be created has no items.
+    let backend = backend::Systemd::with_config_dir(config_dir.path().join("missing"))
+        .with_runner(Arc::new(DryRunRunner::new(runner, output)));
+    assert!(user_startup::get_items_list(&backend).unwrap().is_empty());
+}
+
+#[test]
+fn manifest_test() {
+    use user_startup::manifest::{self, Change, Manifest};
+
+    let (config_dir, backend) = test_init();
+    add_item(&backend, StartupItem::new("oldcmd")).unwrap();
+    let mut manifest = Manifest::from_toml(
+        r#"
+[[item]]
+name = "sync"
+command = "syncthing --no-browser"
+
+[[item]]
+name = "mount"
+command = "rclone mount drive: ~/drive"
+stdout = "rclone.log"
+"#,
+    )
+    .unwrap();
+
+    let changes = manifest::plan(&backend, &manifest, false).unwrap();
+    assert_eq!(changes.len(), 2);
+    assert!(matches!(&changes[0], Change::Add(item) if item.name == "sync"));
+    manifest::apply(&backend, &changes).unwrap();
+    // Applying again changes nothing.
+    assert!(
+        manifest::plan(&backend, &manifest, false)
+            .unwrap()
+            .is_empty()
+    );
+
+    // Changed fields other than the command are listed.
+    manifest.items[1].stdout = Some("other.log".to_string());
+    let changes = manifest::plan(&backend, &manifest, false).unwrap();
+    let absolute = |file| std::path::absolute(file).unwrap().display().to_string();
+    assert_eq!(
+        changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        [format!(
+            "~ mount: rclone mount drive: ~/drive\n    stdout: \"{}\" -> \"{}\"",
+            absolute("rclone.log"),
+            absolute("other.log")
+        )]
+    );
+    manifest.items[1].stdout = Some("rclone.log".to_string());
+
+    manifest.items[0].command = "syncthing".to_string();
+    let changes = manifest::plan(&backend, &manifest, true).unwrap();
+    assert_eq!(
+        changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        [
+            "~ sync: syncthing --no-browser -> syncthing",
+            "- oldcmd: oldcmd"
+        ]
+    );
+    manifest::apply(&backend, &changes).unwrap();
+    assert!(
+        manifest::plan(&backend, &manifest, true)
+            .unwrap()
+            .is_empty()
+    );
+    let mut ids = user_startup::get_items_list(&backend)
+        .unwrap()
+        .into_iter()
+        .map(|item| (item.id, item.command))
+        .collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(
+        ids,
+        [
+            (
+                "mount".to_string(),
+                "rclone mount drive: ~/drive".to_string()
+            ),
+            ("sync".to_string(), "syncthing".to_string())
+        ]
+    );
+
+    // An id taken by a file of another tool is not overwritten.
+    fs::write(config_dir.path().join("other.cmd"), ":: start notepad\r\n").unwrap();
+    manifest.items[0].name = "other".to_string();
+    assert!(matches!(
+        manifest::plan(&backend, &manifest, false),
+        Err(user_startup::Error::NotManaged(_))
+    ));
+}
+
+#[test]
+fn export_import_test() {
+    use user_startup::manifest::{self, Manifest, OnConflict};
+
+    let (_from_dir, from) = test_init();
+    add_item(&from, StartupItem::new("syncthing").stdout("sync.log")).unwrap();
+    add_item(&from, StartupItem::new("rclone mount drive: ~/drive")).unwrap();
+    let exported = Manifest::from_items(&user_startup::get_items_list(&from).unwrap())
+        .to_toml()
+        .unwrap();
+    let manifest = Manifest::from_toml(&exported).unwrap();
+    assert_eq!(manifest.items.len(), 2);
+
+    let (_to_dir, to) = test_init();
+    add_item(&to, StartupItem::new("syncthing --other")).unwrap();
+    assert!(matches!(
+        manifest::import(&to, &manifest, OnConflict::Error),
+        Err(user_startup::Error::AlreadyExists(id)) if id == "syncthing"
+    ));
+    assert_eq!(user_startup::get_items_list(&to).unwrap().len(), 1);
+
+    let added = manifest::import(&to, &manifest, OnConflict::Skip).unwrap();
+    assert_eq!(
+        added
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>(),
+        ["rclone"]
+    );
+    let added = manifest::import(&to, &manifest, OnConflict::Rename).unwrap();
+    assert_eq!(
+        added
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>(),
+        ["syncthing1", "rclone1"]
+    );
+    assert_eq!(
+        added[0].stdout.as_deref(),
+        Some(&*std::path::absolute("sync.log").unwrap().to_string_lossy())
+    );
+}
+
+#[test]
+fn env_test() {
+    let (config_dir, backend) = test_init();
+    let env_file = config_dir.path().join("proxy.env");
+    fs::write(
+        &env_file,
+        "HTTP_PROXY=http://proxy:8080\nNO_PROXY=localhost\n",
+    )
+    .unwrap();
+    let item = add_item(
+        &backend,
+        StartupItem::new("myusrtest")
+            .env("NO_PROXY", "example.com")
+            .env_file(env_file.to_string_lossy()),
+    )
+    .unwrap();
+
+    // The Startup folder copies the variables of the env file into the script.
+    let read = user_startup::get_item(&backend, &item.id).unwrap();
+    assert_eq!(
+        read.env.into_iter().collect::<Vec<_>>(),
+        [
+            ("HTTP_PROXY".to_string(), "http://proxy:8080".to_string()),
+            ("NO_PROXY".to_string(), "example.com".to_string()),
+        ]
+    );
+
+    assert!(matches!(
+        add_item(&backend, StartupItem::new("myusrtest").env("A=B", "1")),
+        Err(user_startup::Error::InvalidEnv(key)) if key == "A=B"
+    ));
+    // A `"` would end the quoted `set` line, running the rest as commands.
+    assert!(matches!(
+        add_item(&backend, StartupItem::new("myusrtest").env("A", r#"x"&calc&""#)),
+        Err(user_startup::Error::InvalidEnv(key)) if key == "A"
+    ));
+    assert_eq!(user_startup::get_items_list(&backend).unwrap().len(), 1);
+}
+
+#[test]
+fn oneshot_test() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let runner = Arc::new(RecordingRunner::new().respond(
+        "systemctl --user show",
+        CommandOutput::ok(
+            "ActiveState=active\nMainPID=0\nExecMainExitTimestamp=Sat 2026-10-17 10:00:01 \
+             UTC\nExecMainStatus=0\n",
+        ),
+    ));
+    let backend = backend::Systemd::with_config_dir(config_dir.path())
+        .with_runner(runner.clone())
+        .with_exe("/usr/bin/user-startup")
+        .offline(false);
+    let item = add_item(&backend, StartupItem::new("myusrtest --mount").once()).unwrap();
+
+    let read = user_startup::get_item(&backend, &item.id).unwrap();
+    assert!(read.oneshot && read.once);
+    assert_eq!(read.restart, user_startup::Restart::Never);
+    let status = read.status(&backend).unwrap();
+    assert_eq!(status.last_exit_code, Some(0));
+
+    // The Startup folder removes the script itself after the first run.
+    let (_config_dir, backend) = test_init();
+    let item = add_item(&backend, StartupItem::new("myusrtest --mount").once()).unwrap();
+    assert!(
+        fs::read_to_string(&item.path)
+            .unwrap()
+            .contains(r#"--remove-on-success "%~f0""#)
+    );
+    let read = user_startup::get_item(&backend, &item.id).unwrap();
+    assert!(read.oneshot && read.once);
+
+    // `once` implies `oneshot` in a manifest too.
+    let manifest = user_startup::manifest::Manifest::from_toml(
+        "[[item]]\nname = 'mount'\ncommand = 'myusrtest --mount'\nonce = true\n",
+    )
+    .unwrap();
+    let item = manifest.items[0].to_item();
+    assert!(item.oneshot && item.once);
+    assert_eq!(item.restart, user_startup::Restart::Never);
 }